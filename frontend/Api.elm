@@ -1,14 +1,21 @@
 module Api exposing
     ( LoginInfo
     , UUID
+    , acceptInvitation
     , article
     , articleSummaryList
     , attemptLogin
     , attemptLogout
+    , attemptTwoFactorLogin
+    , author
     , confirm
+    , confirmTwoFactor
     , delay
+    , disableTwoFactor
     , get
     , getArticle
+    , getAuthor
+    , getTwoFactor
     , hello
     , initLoginInfo
     , login
@@ -16,15 +23,21 @@ module Api exposing
     , msgDecoder
     , post
     , register
+    , resendConfirmation
+    , resetPassword
+    , resetRecoveryCodes
+    , startTwoFactor
     )
 
 import Article
+import Author
 import Http
 import Json.Decode exposing (Decoder, field, list, string)
 import Json.Encode
 import Process
 import RemoteData exposing (WebData)
 import Task
+import TwoFactor
 import Url.Builder
 
 
@@ -38,6 +51,8 @@ type alias LoginInfo =
     , reply : Maybe String
     , pageMessage : Maybe String
     , wrongPassword : Bool
+    , needsCode : Bool
+    , code : String
     }
 
 
@@ -52,6 +67,8 @@ initLoginInfo =
     , reply = Nothing
     , pageMessage = Nothing
     , wrongPassword = False
+    , needsCode = False
+    , code = ""
     }
 
 
@@ -100,6 +117,111 @@ attemptLogin loginInfo toMsg =
         }
 
 
+{-| second step of logging in, after attemptLogin replied that a two-factor code is required
+-}
+attemptTwoFactorLogin : LoginInfo -> (Result Http.Error String -> msg) -> Cmd msg
+attemptTwoFactorLogin loginInfo toMsg =
+    post
+        { endpoint = url [ "login", "two_factor" ]
+        , body = Http.jsonBody <| encodeCode loginInfo.code
+        , expect = Http.expectJson toMsg msgDecoder
+        }
+
+
+encodeCode : String -> Json.Encode.Value
+encodeCode code =
+    Json.Encode.object [ ( "code", Json.Encode.string (String.trim code) ) ]
+
+
+getTwoFactor : (WebData TwoFactor.Status -> msg) -> Cmd msg
+getTwoFactor toMsg =
+    get
+        { endpoint = url [ "two_factor" ]
+        , expect = Http.expectJson (RemoteData.fromResult >> toMsg) TwoFactor.statusDecoder
+        }
+
+
+{-| a new secret for the authenticator app, it is only used once confirmTwoFactor accepts a code from the app
+-}
+startTwoFactor : (Result Http.Error TwoFactor.Setup -> msg) -> Cmd msg
+startTwoFactor toMsg =
+    post
+        { endpoint = url [ "two_factor" ]
+        , body = Http.emptyBody
+        , expect = Http.expectJson toMsg TwoFactor.setupDecoder
+        }
+
+
+{-| replies with the recovery codes, which can't be seen again
+-}
+confirmTwoFactor : String -> (Result Http.Error (List String) -> msg) -> Cmd msg
+confirmTwoFactor code toMsg =
+    post
+        { endpoint = url [ "two_factor", "confirm" ]
+        , body = Http.jsonBody <| encodeCode code
+        , expect = Http.expectJson toMsg TwoFactor.recoveryCodesDecoder
+        }
+
+
+resetRecoveryCodes : String -> (Result Http.Error (List String) -> msg) -> Cmd msg
+resetRecoveryCodes code toMsg =
+    post
+        { endpoint = url [ "two_factor", "recovery_codes" ]
+        , body = Http.jsonBody <| encodeCode code
+        , expect = Http.expectJson toMsg TwoFactor.recoveryCodesDecoder
+        }
+
+
+disableTwoFactor : String -> (Result Http.Error String -> msg) -> Cmd msg
+disableTwoFactor code toMsg =
+    post
+        { endpoint = url [ "two_factor", "disable" ]
+        , body = Http.jsonBody <| encodeCode code
+        , expect = Http.expectJson toMsg msgDecoder
+        }
+
+
+resendConfirmation : String -> (Result Http.Error String -> msg) -> Cmd msg
+resendConfirmation username toMsg =
+    post
+        { endpoint = url [ "confirm", "resend" ]
+        , body = Http.jsonBody <| Json.Encode.object [ ( "username", Json.Encode.string (username |> String.toLower |> String.trim) ) ]
+        , expect = Http.expectJson toMsg msgDecoder
+        }
+
+
+{-| creates the account for a staff invitation, token is from the emailed link
+-}
+acceptInvitation : String -> String -> String -> (Result Http.Error String -> msg) -> Cmd msg
+acceptInvitation token password confirm_ toMsg =
+    post
+        { endpoint = url [ "invitations", token, "accept" ]
+        , body =
+            Http.jsonBody <|
+                Json.Encode.object
+                    [ ( "password", Json.Encode.string password )
+                    , ( "confirm", Json.Encode.string confirm_ )
+                    ]
+        , expect = Http.expectJson toMsg msgDecoder
+        }
+
+
+{-| sets a new password, token is from the emailed reset link
+-}
+resetPassword : String -> String -> String -> (Result Http.Error String -> msg) -> Cmd msg
+resetPassword token password confirm_ toMsg =
+    post
+        { endpoint = url [ "password_reset", token ]
+        , body =
+            Http.jsonBody <|
+                Json.Encode.object
+                    [ ( "password", Json.Encode.string password )
+                    , ( "confirm", Json.Encode.string confirm_ )
+                    ]
+        , expect = Http.expectJson toMsg msgDecoder
+        }
+
+
 attemptLogout : (Result Http.Error () -> msg) -> Cmd msg
 attemptLogout toMsg =
     post
@@ -125,6 +247,14 @@ getArticle toMsg id =
         }
 
 
+getAuthor : (WebData Author.Author -> msg) -> String -> Maybe Int -> Cmd msg
+getAuthor toMsg username after =
+    get
+        { endpoint = author username after
+        , expect = Http.expectJson (RemoteData.fromResult >> toMsg) Author.authorDecoder
+        }
+
+
 unwrap : Endpoint -> String
 unwrap (Endpoint s) =
     s
@@ -165,6 +295,19 @@ article id =
     url [ "article", String.fromInt id ]
 
 
+author : String -> Maybe Int -> Endpoint
+author username after =
+    Url.Builder.absolute [ "api", "authors", username ]
+        (case after of
+            Just id ->
+                [ Url.Builder.int "after" id ]
+
+            Nothing ->
+                []
+        )
+        |> Endpoint
+
+
 confirm : String -> String
 confirm invitation =
     Url.Builder.absolute [ "api", "confirm", invitation ] []
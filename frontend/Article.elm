@@ -1,9 +1,9 @@
-module Article exposing (Article, ArticleSummary, articleDecoder, articleSummaryCard, articleSummaryDecoder, summarize, time, timeToDate)
+module Article exposing (Article, ArticleSummary, Byline, articleDecoder, articleSummaryCard, articleSummaryDecoder, bylineText, summarize, time, timeToDate, viewBylines)
 
 import Html exposing (..)
 import Html.Attributes exposing (class)
-import Html.Events exposing (onClick)
-import Json.Decode exposing (Decoder, field, int, nullable, string)
+import Html.Events exposing (onClick, stopPropagationOn)
+import Json.Decode exposing (Decoder, field, int, list, nullable, string)
 import Route
 import Style
 import Time
@@ -16,13 +16,75 @@ time =
         (field "secs_since_epoch" int)
 
 
+{-| username is Nothing for guest authors, who have no account
+-}
+type alias Byline =
+    { name : String
+    , bio : Maybe String
+    , username : Maybe String
+    }
+
+
+bylineDecoder : Decoder Byline
+bylineDecoder =
+    Json.Decode.map3 Byline
+        (field "name" string)
+        (field "bio" (nullable string))
+        (field "username" (nullable string))
+
+
+bylineText : List Byline -> String
+bylineText bylines =
+    bylines
+        |> List.map .name
+        |> String.join "、"
+
+
+{-| Authors with an account link to their profile, guests are plain text
+-}
+viewBylines : List Byline -> List (Html msg)
+viewBylines bylines =
+    bylines
+        |> List.map
+            (\byline ->
+                case byline.username of
+                    Just username ->
+                        a [ Route.href (Route.Author username), class "hover:underline" ] [ text byline.name ]
+
+                    Nothing ->
+                        text byline.name
+            )
+        |> List.intersperse (text "、")
+
+
+{-| The whole card opens the article, so clicking an author must not bubble up to it
+-}
+viewCardBylines : (Route.Route -> msg) -> List Byline -> List (Html msg)
+viewCardBylines toMsg bylines =
+    bylines
+        |> List.map
+            (\byline ->
+                case byline.username of
+                    Just username ->
+                        span
+                            [ class "hover:underline"
+                            , stopPropagationOn "click" (Json.Decode.succeed ( toMsg (Route.Author username), True ))
+                            ]
+                            [ text byline.name ]
+
+                    Nothing ->
+                        text byline.name
+            )
+        |> List.intersperse (text "、")
+
+
 type alias Article =
     { articleID : Int
     , headlineCN : String
     , dateCreated : Time.Posix
     , articleBody : String
     , summary : String
-    , author : String
+    , bylines : List Byline
     , image : Maybe String
     }
 
@@ -35,7 +97,7 @@ articleDecoder =
         (field "date_created" time)
         (field "article_body" string)
         (field "summary" string)
-        (field "author" string)
+        (field "bylines" (list bylineDecoder))
         (field "image" (nullable string))
 
 
@@ -44,7 +106,7 @@ type alias ArticleSummary =
     , headlineCN : String
     , dateCreated : Time.Posix
     , summary : String
-    , author : String
+    , bylines : List Byline
     , image : Maybe String
     }
 
@@ -56,7 +118,7 @@ articleSummaryDecoder =
         (field "headline_cn" string)
         (field "date_created" time)
         (field "summary" string)
-        (field "author" string)
+        (field "bylines" (list bylineDecoder))
         (field "image" (nullable string))
 
 
@@ -67,7 +129,7 @@ summarize article =
         article.headlineCN
         article.dateCreated
         article.summary
-        article.author
+        article.bylines
         article.image
 
 
@@ -77,18 +139,16 @@ articleSummaryCard toMsg articleSummary =
         [ class "group hover:bg-gray-100 w-full md:h-48 h-md block md:flex md:flex-row"
         , onClick <| toMsg (Route.Article articleSummary.articleID)
         ]
-        [ div
-            [ class "float-none h-48 md:h-auto bg-cover bg-center overflow-hidden md:w-48 flex-shrink-0 m-2"
-            , Style.maybeBackgroundImage articleSummary.image
-            ]
-            []
+        [ Style.responsiveImage "(min-width: 768px) 12rem, 100vw"
+            articleSummary.image
+            [ class "float-none w-full h-48 md:h-auto object-cover object-center overflow-hidden md:w-48 flex-shrink-0 m-2" ]
         , div
             [ class "md:flex md:flex-col md:justify-between md:flex-grow md:px-4" ]
             [ h3 [ class "font-bold text-3xl" ] [ text articleSummary.headlineCN ]
             , h1 [ class "font-light text-lg overflow-auto" ] [ text articleSummary.summary ]
             , div
                 []
-                [ span [ class "text-sm mr-4" ] [ text articleSummary.author ]
+                [ span [ class "text-sm mr-4" ] (viewCardBylines toMsg articleSummary.bylines)
                 , span [ class "text-sm" ] [ articleSummary.dateCreated |> timeToDate |> text ]
                 ]
             ]
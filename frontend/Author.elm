@@ -0,0 +1,27 @@
+module Author exposing (Author, authorDecoder)
+
+import Article
+import Json.Decode exposing (Decoder, field, int, list, nullable, string)
+
+
+{-| A page of the author's profile, next is the id to pass as after for the following page
+-}
+type alias Author =
+    { username : String
+    , displayName : Maybe String
+    , bio : Maybe String
+    , avatar : Maybe String
+    , articles : List Article.ArticleSummary
+    , next : Maybe Int
+    }
+
+
+authorDecoder : Decoder Author
+authorDecoder =
+    Json.Decode.map6 Author
+        (field "username" string)
+        (field "display_name" (nullable string))
+        (field "bio" (nullable string))
+        (field "avatar" (nullable string))
+        (field "articles" (list Article.articleSummaryDecoder))
+        (field "next" (nullable int))
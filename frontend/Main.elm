@@ -12,12 +12,17 @@ import Localization
 import Navbar
 import Page
 import Page.Article
+import Page.Author
 import Page.Blank
+import Page.Confirmation
 import Page.Home
+import Page.Invitation
 import Page.Login
 import Page.Logout
 import Page.NotFound
 import Page.Register
+import Page.ResetPassword
+import Page.TwoFactor
 import Page.WriteArticle
 import Route
 import Session
@@ -51,6 +56,11 @@ type Model
     | Logout Page.Logout.Model
     | Register Page.Register.Model
     | Article Page.Article.Model
+    | Author Page.Author.Model
+    | Confirmation Page.Confirmation.Model
+    | Invitation Page.Invitation.Model
+    | ResetPassword Page.ResetPassword.Model
+    | TwoFactor Page.TwoFactor.Model
     | WriteArticle Page.WriteArticle.Model
 
 
@@ -76,6 +86,11 @@ type Msg
     | GotSessionMsg Session.Msg
     | GotLogoutMsg Page.Logout.Msg
     | GotArticleMsg Page.Article.Msg
+    | GotAuthorMsg Page.Author.Msg
+    | GotConfirmationMsg Page.Confirmation.Msg
+    | GotInvitationMsg Page.Invitation.Msg
+    | GotResetPasswordMsg Page.ResetPassword.Msg
+    | GotTwoFactorMsg Page.TwoFactor.Msg
     | GotWriteArticleMsg Page.WriteArticle.Msg
 
 
@@ -103,6 +118,21 @@ getSession model =
         Article subModel ->
             subModel.session
 
+        Author subModel ->
+            subModel.session
+
+        Confirmation subModel ->
+            subModel.session
+
+        Invitation subModel ->
+            subModel.session
+
+        ResetPassword subModel ->
+            subModel.session
+
+        TwoFactor subModel ->
+            subModel.session
+
         WriteArticle subModel ->
             subModel.session
 
@@ -131,6 +161,21 @@ updateSession session model =
         Article subModel ->
             Article { subModel | session = session }
 
+        Author subModel ->
+            Author { subModel | session = session }
+
+        Confirmation subModel ->
+            Confirmation { subModel | session = session }
+
+        Invitation subModel ->
+            Invitation { subModel | session = session }
+
+        ResetPassword subModel ->
+            ResetPassword { subModel | session = session }
+
+        TwoFactor subModel ->
+            TwoFactor { subModel | session = session }
+
         WriteArticle subModel ->
             WriteArticle { subModel | session = session }
 
@@ -176,30 +221,50 @@ changeRouteTo maybeRoute model =
             else
                 notFound
     in
-    case maybeRoute of
-        Nothing ->
-            notFound
+    if Session.needsTwoFactor session && maybeRoute /= Just Route.TwoFactor && maybeRoute /= Just Route.Logout then
+        -- the server refuses everything else until two-factor authentication is set up
+        model |> withCmd (Route.replaceUrl (Session.getKey session) Route.TwoFactor)
 
-        Just Route.Logout ->
-            loggedIn (Page.Logout.init session |> updateWith GotLogoutMsg Logout)
+    else
+        case maybeRoute of
+            Nothing ->
+                notFound
 
-        Just Route.Home ->
-            Page.Home.init session |> updateWith GotHomeMsg Home
+            Just Route.Logout ->
+                loggedIn (Page.Logout.init session |> updateWith GotLogoutMsg Logout)
 
-        Just Route.Login ->
-            loggedOut (Page.Login.init session |> updateWith GotLoginMsg Login)
+            Just Route.Home ->
+                Page.Home.init session |> updateWith GotHomeMsg Home
 
-        Just Route.Register ->
-            loggedIn (Page.Register.init session |> updateWith GotRegisterMsg Register)
+            Just Route.Login ->
+                loggedOut (Page.Login.init session |> updateWith GotLoginMsg Login)
 
-        Just (Route.Article id) ->
-            Page.Article.init session id |> updateWith GotArticleMsg Article
+            Just Route.Register ->
+                loggedIn (Page.Register.init session |> updateWith GotRegisterMsg Register)
 
-        Just (Route.WriteArticle uuid) ->
-            needsRole Session.Author (Page.WriteArticle.init session uuid |> updateWith GotWriteArticleMsg WriteArticle)
+            Just (Route.Article id) ->
+                Page.Article.init session id |> updateWith GotArticleMsg Article
 
-        Just Route.Empty ->
-            model |> withNoCmd
+            Just (Route.Author username) ->
+                Page.Author.init session username |> updateWith GotAuthorMsg Author
+
+            Just (Route.Confirmation result) ->
+                Page.Confirmation.init session result |> updateWith GotConfirmationMsg Confirmation
+
+            Just (Route.Invitation token) ->
+                Page.Invitation.init session token |> updateWith GotInvitationMsg Invitation
+
+            Just (Route.ResetPassword token) ->
+                Page.ResetPassword.init session token |> updateWith GotResetPasswordMsg ResetPassword
+
+            Just Route.TwoFactor ->
+                loggedIn (Page.TwoFactor.init session |> updateWith GotTwoFactorMsg TwoFactor)
+
+            Just (Route.WriteArticle uuid) ->
+                needsRole Session.Author (Page.WriteArticle.init session uuid |> updateWith GotWriteArticleMsg WriteArticle)
+
+            Just Route.Empty ->
+                model |> withNoCmd
 
 
 update : Msg -> Model -> ( Model, Cmd Msg )
@@ -236,6 +301,26 @@ update msg model =
             Page.Article.update subMsg subModel
                 |> updateWith GotArticleMsg Article
 
+        ( GotAuthorMsg subMsg, Author subModel ) ->
+            Page.Author.update subMsg subModel
+                |> updateWith GotAuthorMsg Author
+
+        ( GotConfirmationMsg subMsg, Confirmation subModel ) ->
+            Page.Confirmation.update subMsg subModel
+                |> updateWith GotConfirmationMsg Confirmation
+
+        ( GotInvitationMsg subMsg, Invitation subModel ) ->
+            Page.Invitation.update subMsg subModel
+                |> updateWith GotInvitationMsg Invitation
+
+        ( GotResetPasswordMsg subMsg, ResetPassword subModel ) ->
+            Page.ResetPassword.update subMsg subModel
+                |> updateWith GotResetPasswordMsg ResetPassword
+
+        ( GotTwoFactorMsg subMsg, TwoFactor subModel ) ->
+            Page.TwoFactor.update subMsg subModel
+                |> updateWith GotTwoFactorMsg TwoFactor
+
         ( GotWriteArticleMsg subMsg, WriteArticle subModel ) ->
             Page.WriteArticle.update subMsg subModel
                 |> updateWith GotWriteArticleMsg WriteArticle
@@ -283,6 +368,21 @@ subscriptions model =
         Article subModel ->
             Sub.map GotArticleMsg (Page.Article.subscriptions subModel)
 
+        Author subModel ->
+            Sub.map GotAuthorMsg (Page.Author.subscriptions subModel)
+
+        Confirmation subModel ->
+            Sub.map GotConfirmationMsg (Page.Confirmation.subscriptions subModel)
+
+        Invitation subModel ->
+            Sub.map GotInvitationMsg (Page.Invitation.subscriptions subModel)
+
+        ResetPassword subModel ->
+            Sub.map GotResetPasswordMsg (Page.ResetPassword.subscriptions subModel)
+
+        TwoFactor subModel ->
+            Sub.map GotTwoFactorMsg (Page.TwoFactor.subscriptions subModel)
+
         WriteArticle subModel ->
             Sub.map GotWriteArticleMsg (Page.WriteArticle.subscriptions subModel)
 
@@ -338,5 +438,20 @@ view model =
         Article subModel ->
             viewPage Page.Article GotArticleMsg (Page.Article.view subModel)
 
+        Author subModel ->
+            viewPage Page.Author GotAuthorMsg (Page.Author.view subModel)
+
+        Confirmation subModel ->
+            viewPage Page.Confirmation GotConfirmationMsg (Page.Confirmation.view subModel)
+
+        Invitation subModel ->
+            viewPage Page.Invitation GotInvitationMsg (Page.Invitation.view subModel)
+
+        ResetPassword subModel ->
+            viewPage Page.ResetPassword GotResetPasswordMsg (Page.ResetPassword.view subModel)
+
+        TwoFactor subModel ->
+            viewPage Page.TwoFactor GotTwoFactorMsg (Page.TwoFactor.view subModel)
+
         WriteArticle subModel ->
             viewPage Page.WriteArticle GotWriteArticleMsg (Page.WriteArticle.view subModel)
@@ -80,6 +80,7 @@ view session =
             , a [ hiddenWhenLoggedIn session, class "hover:text-gray-300 md:ml-2", Route.href Route.Register ] [ text_ "Register" ]
             , div [ forAdmin session ]
                 [ a [ class "hover:text-gray-300 md:mr-2", Route.href (Route.WriteArticle "0") ] [ text "Create Article" ] ]
+            , a [ hiddenWhenLoggedOut session, class "hover:text-gray-300 md:mr-2", Route.href Route.TwoFactor ] [ text "Security" ]
             , a [ hiddenWhenLoggedOut session, class "hover:text-gray-300", Route.href Route.Logout ] [ text "Logout" ]
             ]
         ]
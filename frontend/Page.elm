@@ -18,6 +18,11 @@ type Page
     | Register
     | Logout
     | Article
+    | Author
+    | Confirmation
+    | Invitation
+    | ResetPassword
+    | TwoFactor
     | WriteArticle
 
 
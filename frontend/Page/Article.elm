@@ -78,14 +78,12 @@ viewArticle article =
     Html.div
         [ class "md:col-start-2 md:col-span-2 m-4 md:m-0 md:mt-4" ]
         [ h1 [ class "w-full text-center text-3xl font-bold" ] [ text article.headlineCN ]
-        , h3 [ class "text-sm text-center md:text-left" ] [ text article.author ]
+        , h3 [ class "text-sm text-center md:text-left" ] (Article.viewBylines article.bylines)
         , h3 [ class "text-xs text-center md:text-left" ] [ text <| Article.timeToDate article.dateCreated ]
         , Style.divider
-        , div
-            [ class "w-full h-48 bg-fixed bg-contain bg-center"
-            , Style.maybeBackgroundImage article.image
-            ]
-            []
+        , Style.responsiveImage "(min-width: 768px) 50vw, 100vw"
+            article.image
+            [ class "w-full h-48 object-contain object-center" ]
         , p [] [ text article.articleBody ]
         , p [] [ text article.articleBody ]
         , p [] [ text article.articleBody ]
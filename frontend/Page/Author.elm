@@ -0,0 +1,142 @@
+module Page.Author exposing (Model, Msg(..), init, subscriptions, update, view)
+
+import Api
+import Article
+import Author
+import Cmd.Extra exposing (withCmd, withNoCmd)
+import Html exposing (..)
+import Html.Attributes exposing (class)
+import Html.Events exposing (onClick)
+import RemoteData
+import Route
+import Session exposing (..)
+import Style
+
+
+type alias Model =
+    { author : RemoteData.WebData Author.Author
+    , loadingMore : Bool
+    , session : Session
+    }
+
+
+type Msg
+    = GetAuthor (RemoteData.WebData Author.Author)
+    | GetMore (RemoteData.WebData Author.Author)
+    | LoadMore
+    | Article Route.Route
+
+
+init : Session -> String -> ( Model, Cmd Msg )
+init session username =
+    ( { author = RemoteData.NotAsked, loadingMore = False, session = session }, Api.getAuthor GetAuthor username Nothing )
+
+
+update : Msg -> Model -> ( Model, Cmd Msg )
+update msg model =
+    case msg of
+        GetAuthor response ->
+            { model | author = response } |> withNoCmd
+
+        LoadMore ->
+            case model.author of
+                RemoteData.Success author ->
+                    case author.next of
+                        Just after ->
+                            { model | loadingMore = True } |> withCmd (Api.getAuthor GetMore author.username (Just after))
+
+                        Nothing ->
+                            model |> withNoCmd
+
+                _ ->
+                    model |> withNoCmd
+
+        -- keep the articles already shown if the next page fails
+        GetMore response ->
+            case ( model.author, response ) of
+                ( RemoteData.Success author, RemoteData.Success more ) ->
+                    { model
+                        | author = RemoteData.Success { author | articles = author.articles ++ more.articles, next = more.next }
+                        , loadingMore = False
+                    }
+                        |> withNoCmd
+
+                _ ->
+                    { model | loadingMore = False } |> withNoCmd
+
+        Article route ->
+            model |> withCmd (Route.pushUrl (Session.getKey model.session) route)
+
+
+view : Model -> { title : String, content : Html Msg }
+view model =
+    let
+        title =
+            case model.author of
+                RemoteData.Success author ->
+                    Maybe.withDefault author.username author.displayName
+
+                RemoteData.Failure _ ->
+                    "Error"
+
+                _ ->
+                    "Loading"
+    in
+    { title = title
+    , content =
+        Html.div
+            [ class "w-full md:grid md:grid-cols-4 md:gap-4" ]
+        <|
+            case model.author of
+                RemoteData.Failure _ ->
+                    [ Html.div [ class "md:col-start-2 md:col-span-2 m4 md:m-0 flex flex-row justify-center text-center" ] [ text "Failed" ] ]
+
+                RemoteData.Success author ->
+                    viewProfile author
+                        :: List.map
+                            (\article ->
+                                Html.div
+                                    [ class "md:col-start-2 md:col-span-2 m-4 md:m-0" ]
+                                    [ Article.articleSummaryCard Article article ]
+                            )
+                            author.articles
+                        ++ [ viewMore model.loadingMore author ]
+
+                _ ->
+                    [ Html.div [ class "md:col-start-2 md:col-span-2 m4 md:m-0 flex flex-row justify-center" ] [ Style.loadingIcon ] ]
+    }
+
+
+viewProfile : Author.Author -> Html msg
+viewProfile author =
+    Html.div
+        [ class "md:col-start-2 md:col-span-2 m-4 md:m-0 md:mt-4 flex flex-row items-center" ]
+        [ Style.responsiveImage "6rem"
+            author.avatar
+            [ class "w-24 h-24 rounded-full object-cover object-center flex-shrink-0 mr-4" ]
+        , Html.div
+            []
+            [ h1 [ class "text-3xl font-bold" ] [ text <| Maybe.withDefault author.username author.displayName ]
+            , p [ class "text-sm" ] [ text <| Maybe.withDefault "" author.bio ]
+            ]
+        ]
+
+
+viewMore : Bool -> Author.Author -> Html Msg
+viewMore loadingMore author =
+    Html.div
+        [ class "md:col-start-2 md:col-span-2 m-4 md:m-0 flex flex-row justify-center" ]
+        (if loadingMore then
+            [ Style.loadingIcon ]
+
+         else if author.next == Nothing then
+            []
+
+         else
+            [ Style.formButtonNoSubmit "More" [ onClick LoadMore ] ]
+        )
+
+
+subscriptions : Model -> Sub Msg
+subscriptions model =
+    Sub.none
@@ -0,0 +1,106 @@
+module Page.Confirmation exposing (Model, Msg(..), init, subscriptions, update, view)
+
+import Api
+import Cmd.Extra exposing (withCmd, withNoCmd)
+import Html exposing (..)
+import Html.Attributes exposing (class, id)
+import Html.Events
+import Http
+import Route
+import Session
+import Style
+
+
+{-| result is where /api/confirm redirected to: success, expired or invalid
+-}
+type alias Model =
+    { result : String
+    , username : String
+    , reply : Maybe String
+    , session : Session.Session
+    }
+
+
+type Msg
+    = EnteredUsername String
+    | SubmittedResend
+    | SentResend (Result Http.Error String)
+
+
+init : Session.Session -> String -> ( Model, Cmd Msg )
+init session result =
+    { result = result
+    , username = ""
+    , reply = Nothing
+    , session = session
+    }
+        |> withNoCmd
+
+
+update : Msg -> Model -> ( Model, Cmd Msg )
+update msg model =
+    case msg of
+        EnteredUsername s ->
+            { model | username = s } |> withNoCmd
+
+        SubmittedResend ->
+            { model | reply = Nothing } |> withCmd (Api.resendConfirmation model.username SentResend)
+
+        SentResend (Ok s) ->
+            { model | reply = Just s } |> withNoCmd
+
+        SentResend (Err _) ->
+            { model | reply = Just "Could not send the email, please try again later." } |> withNoCmd
+
+
+view : Model -> { title : String, content : Html Msg }
+view model =
+    { title = "Confirmation"
+    , content =
+        main_ [ id "content", class "container" ] <|
+            case model.result of
+                "success" ->
+                    [ text "Your email address is confirmed." |> Style.bodyAlert
+                    , Style.linkAlert "Ready to start?" "Sign in." Route.Login
+                    ]
+
+                "expired" ->
+                    [ text "This confirmation link has expired." |> Style.bodyAlert
+                    , viewResend model
+                    ]
+
+                _ ->
+                    [ text "This confirmation link is not valid. It may have been used already." |> Style.bodyAlert
+                    , viewResend model
+                    , Style.linkAlert "Already confirmed?" "Sign in." Route.Login
+                    ]
+    }
+
+
+viewResend : Model -> Html Msg
+viewResend model =
+    Html.div
+        [ class "w-full max-w-xs container fade-in" ]
+        [ Html.form
+            [ Html.Events.onSubmit SubmittedResend
+            , class "bg-white shadow-md rounded px-8 pt-6 pb-8 m-4"
+            ]
+            [ Style.formInputField "Email address"
+                Nothing
+                [ Html.Events.onInput EnteredUsername
+                , Html.Attributes.value model.username
+                ]
+            , Style.formButton "Send a new link" []
+            , case model.reply of
+                Just s ->
+                    Html.div [ class "text-sm italic" ] [ text s ]
+
+                Nothing ->
+                    Html.div [] []
+            ]
+        ]
+
+
+subscriptions : Model -> Sub Msg
+subscriptions model =
+    Sub.none
@@ -0,0 +1,137 @@
+module Page.Invitation exposing (Model, Msg(..), init, subscriptions, update, view)
+
+import Api
+import Cmd.Extra exposing (withCmd, withNoCmd)
+import Html exposing (..)
+import Html.Attributes exposing (class, id)
+import Html.Events
+import Http
+import Route
+import Session
+import Style
+
+
+{-| token is from the link in the staff invitation email, the invited address becomes the username
+-}
+type alias Model =
+    { token : String
+    , password : String
+    , confirm : String
+    , accepted : Bool
+    , reply : Maybe String
+    , session : Session.Session
+    }
+
+
+type Msg
+    = EnteredPassword String
+    | EnteredConfirm String
+    | SubmittedForm
+    | SentAccept (Result Http.Error String)
+
+
+init : Session.Session -> String -> ( Model, Cmd Msg )
+init session token =
+    { token = token
+    , password = ""
+    , confirm = ""
+    , accepted = False
+    , reply = Nothing
+    , session = session
+    }
+        |> withNoCmd
+
+
+update : Msg -> Model -> ( Model, Cmd Msg )
+update msg model =
+    case msg of
+        EnteredPassword s ->
+            { model | password = s } |> withNoCmd
+
+        EnteredConfirm s ->
+            { model | confirm = s } |> withNoCmd
+
+        SubmittedForm ->
+            if String.length model.password < 8 then
+                { model | reply = Just "Password must be 8 or more characters" } |> withNoCmd
+
+            else if model.password /= model.confirm then
+                { model | reply = Just "Passwords don't match" } |> withNoCmd
+
+            else
+                { model | reply = Nothing } |> withCmd (Api.acceptInvitation model.token model.password model.confirm SentAccept)
+
+        SentAccept (Ok _) ->
+            { model | accepted = True } |> withNoCmd
+
+        SentAccept (Err e) ->
+            { model | reply = Just (errorReply e) } |> withNoCmd
+
+
+errorReply : Http.Error -> String
+errorReply e =
+    case e of
+        Http.BadStatus 404 ->
+            "This invitation is not valid. It may have been used or revoked already."
+
+        Http.BadStatus 409 ->
+            "An account with this email address already exists."
+
+        Http.BadStatus 410 ->
+            "This invitation has expired. Please ask for a new one."
+
+        _ ->
+            "Something went wrong, please try again later."
+
+
+view : Model -> { title : String, content : Html Msg }
+view model =
+    { title = "Invitation"
+    , content =
+        main_ [ id "content", class "container" ] <|
+            if model.accepted then
+                [ text "Your account is ready." |> Style.bodyAlert
+                , Style.linkAlert "Ready to start?" "Sign in." Route.Login
+                ]
+
+            else
+                [ text "You have been invited to join the team. Choose a password to set up your account." |> Style.bodyAlert
+                , viewForm model
+                ]
+    }
+
+
+viewForm : Model -> Html Msg
+viewForm model =
+    Html.div
+        [ class "w-full max-w-xs container fade-in" ]
+        [ Html.form
+            [ Html.Events.onSubmit SubmittedForm
+            , class "bg-white shadow-md rounded px-8 pt-6 pb-8 m-4"
+            ]
+            [ Style.formInputField "Password"
+                Nothing
+                [ Html.Events.onInput EnteredPassword
+                , Html.Attributes.value model.password
+                , Html.Attributes.type_ "password"
+                ]
+            , Style.formInputField "Repeat Password"
+                Nothing
+                [ Html.Events.onInput EnteredConfirm
+                , Html.Attributes.value model.confirm
+                , Html.Attributes.type_ "password"
+                ]
+            , Style.formButton "Create account" []
+            , case model.reply of
+                Just s ->
+                    Html.div [ class "text-sm text-red-500 italic" ] [ text s ]
+
+                Nothing ->
+                    Html.div [] []
+            ]
+        ]
+
+
+subscriptions : Model -> Sub Msg
+subscriptions model =
+    Sub.none
@@ -34,7 +34,9 @@ type FormMsg
     = SubmittedForm
     | EnteredUsername String
     | EnteredPassword String
+    | EnteredCode String
     | SentLogin (Result Http.Error String)
+    | SentCode (Result Http.Error String)
     | LoggedIn
 
 
@@ -66,7 +68,14 @@ updateForm : FormMsg -> Api.LoginInfo -> ( Api.LoginInfo, Cmd FormMsg )
 updateForm msg form =
     case msg of
         SubmittedForm ->
-            { form | wrongPassword = False, pageMessage = Nothing, reply = Nothing } |> withCmd (Api.attemptLogin form SentLogin)
+            { form | wrongPassword = False, pageMessage = Nothing, reply = Nothing }
+                |> withCmd
+                    (if form.needsCode then
+                        Api.attemptTwoFactorLogin form SentCode
+
+                     else
+                        Api.attemptLogin form SentLogin
+                    )
 
         EnteredUsername s ->
             { form | username = s } |> withNoCmd
@@ -74,11 +83,17 @@ updateForm msg form =
         EnteredPassword s ->
             { form | password = s } |> withNoCmd
 
+        EnteredCode s ->
+            { form | code = s } |> withNoCmd
+
         SentLogin rs ->
             case rs of
                 Ok "Success" ->
                     { form | wrongPassword = False, pageMessage = Just "Logged in." } |> withCmd (Api.delay 2000 LoggedIn)
 
+                Ok "Two-factor code required" ->
+                    { form | wrongPassword = False, needsCode = True, code = "" } |> withNoCmd
+
                 Ok "AuthenticationError(\"Wrong password\")" ->
                     { form | reply = Just "Wrong password.", wrongPassword = True } |> withNoCmd
 
@@ -91,6 +106,27 @@ updateForm msg form =
                 Ok s ->
                     { form | reply = Just s, wrongPassword = False } |> withNoCmd
 
+                Err (Http.BadStatus 429) ->
+                    { form | reply = Just "Too many attempts. Please wait a while and try again.", wrongPassword = False } |> withNoCmd
+
+                Err _ ->
+                    form |> withNoCmd
+
+        SentCode rs ->
+            case rs of
+                Ok _ ->
+                    { form | pageMessage = Just "Logged in." } |> withCmd (Api.delay 2000 LoggedIn)
+
+                Err (Http.BadStatus 403) ->
+                    { form | reply = Just "Wrong code." } |> withNoCmd
+
+                -- the password step has to be done again after a few minutes
+                Err (Http.BadStatus 404) ->
+                    { form | reply = Just "That took too long. Please sign in again.", needsCode = False, password = "" } |> withNoCmd
+
+                Err (Http.BadStatus 429) ->
+                    { form | reply = Just "Too many attempts. Please wait a while and try again." } |> withNoCmd
+
                 Err _ ->
                     form |> withNoCmd
 
@@ -115,6 +151,40 @@ view model =
 
 viewForm : Api.LoginInfo -> Html FormMsg
 viewForm form =
+    if form.needsCode then
+        viewCodeForm form
+
+    else
+        viewPasswordForm form
+
+
+viewCodeForm : Api.LoginInfo -> Html FormMsg
+viewCodeForm form =
+    Html.div
+        [ class "w-full max-w-xs container fade-in" ]
+        [ Html.form
+            [ Html.Events.onSubmit SubmittedForm
+            , class "bg-white shadow-md rounded px-8 pt-6 pb-8 m-4"
+            ]
+            [ Style.formInputField "Code from your authenticator app, or a recovery code"
+                Nothing
+                [ Html.Events.onInput EnteredCode
+                , Html.Attributes.value form.code
+                , Html.Attributes.attribute "autocomplete" "one-time-code"
+                ]
+            , Style.formButton "Verify" []
+            , case form.reply of
+                Just s ->
+                    Html.div [ class "text-sm text-red-500 italic" ] [ text s ]
+
+                Nothing ->
+                    Html.div [] []
+            ]
+        ]
+
+
+viewPasswordForm : Api.LoginInfo -> Html FormMsg
+viewPasswordForm form =
     Html.div
         [ class "w-full max-w-xs container fade-in" ]
         [ Html.form
@@ -118,6 +118,9 @@ updateForm msg form =
                 Ok s ->
                     { form | reply = Just s, usernameExists = False } |> withNoCmd
 
+                Err (Http.BadStatus 429) ->
+                    { form | reply = Just "Too many attempts. Please wait a while and try again.", usernameExists = False } |> withNoCmd
+
                 Err _ ->
                     form |> withNoCmd
 
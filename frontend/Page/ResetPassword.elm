@@ -0,0 +1,137 @@
+module Page.ResetPassword exposing (Model, Msg(..), init, subscriptions, update, view)
+
+import Api
+import Cmd.Extra exposing (withCmd, withNoCmd)
+import Html exposing (..)
+import Html.Attributes exposing (class, id)
+import Html.Events
+import Http
+import Route
+import Session
+import Style
+
+
+{-| token is from the link in the password reset email
+-}
+type alias Model =
+    { token : String
+    , password : String
+    , confirm : String
+    , changed : Bool
+    , reply : Maybe String
+    , session : Session.Session
+    }
+
+
+type Msg
+    = EnteredPassword String
+    | EnteredConfirm String
+    | SubmittedForm
+    | SentReset (Result Http.Error String)
+
+
+init : Session.Session -> String -> ( Model, Cmd Msg )
+init session token =
+    { token = token
+    , password = ""
+    , confirm = ""
+    , changed = False
+    , reply = Nothing
+    , session = session
+    }
+        |> withNoCmd
+
+
+update : Msg -> Model -> ( Model, Cmd Msg )
+update msg model =
+    case msg of
+        EnteredPassword s ->
+            { model | password = s } |> withNoCmd
+
+        EnteredConfirm s ->
+            { model | confirm = s } |> withNoCmd
+
+        SubmittedForm ->
+            if String.length model.password < 8 then
+                { model | reply = Just "Password must be 8 or more characters" } |> withNoCmd
+
+            else if model.password /= model.confirm then
+                { model | reply = Just "Passwords don't match" } |> withNoCmd
+
+            else
+                { model | reply = Nothing } |> withCmd (Api.resetPassword model.token model.password model.confirm SentReset)
+
+        SentReset (Ok _) ->
+            { model | changed = True } |> withNoCmd
+
+        SentReset (Err e) ->
+            { model | reply = Just (errorReply e) } |> withNoCmd
+
+
+errorReply : Http.Error -> String
+errorReply e =
+    case e of
+        Http.BadStatus 404 ->
+            "This reset link is not valid. It may have been used already."
+
+        Http.BadStatus 410 ->
+            "This reset link has expired. Please ask for a new one."
+
+        Http.BadStatus 429 ->
+            "Too many attempts. Please wait a while and try again."
+
+        _ ->
+            "Something went wrong, please try again later."
+
+
+view : Model -> { title : String, content : Html Msg }
+view model =
+    { title = "Reset password"
+    , content =
+        main_ [ id "content", class "container" ] <|
+            if model.changed then
+                [ text "Your password has been changed, and you have been signed out everywhere else." |> Style.bodyAlert
+                , Style.linkAlert "Ready to start?" "Sign in." Route.Login
+                ]
+
+            else
+                [ text "Choose a new password for your account." |> Style.bodyAlert
+                , viewForm model
+                ]
+    }
+
+
+viewForm : Model -> Html Msg
+viewForm model =
+    Html.div
+        [ class "w-full max-w-xs container fade-in" ]
+        [ Html.form
+            [ Html.Events.onSubmit SubmittedForm
+            , class "bg-white shadow-md rounded px-8 pt-6 pb-8 m-4"
+            ]
+            [ Style.formInputField "New Password"
+                Nothing
+                [ Html.Events.onInput EnteredPassword
+                , Html.Attributes.value model.password
+                , Html.Attributes.type_ "password"
+                ]
+            , Style.formInputField "Repeat Password"
+                Nothing
+                [ Html.Events.onInput EnteredConfirm
+                , Html.Attributes.value model.confirm
+                , Html.Attributes.type_ "password"
+                ]
+            , Style.formButton "Change password" []
+            , case model.reply of
+                Just s ->
+                    Html.div [ class "text-sm text-red-500 italic" ] [ text s ]
+
+                Nothing ->
+                    Html.div [] []
+            ]
+        ]
+
+
+subscriptions : Model -> Sub Msg
+subscriptions model =
+    Sub.none
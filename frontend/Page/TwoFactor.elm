@@ -0,0 +1,250 @@
+module Page.TwoFactor exposing (Model, Msg(..), init, subscriptions, update, view)
+
+import Api
+import Cmd.Extra exposing (withCmd, withNoCmd)
+import Html exposing (..)
+import Html.Attributes exposing (class, id)
+import Html.Events
+import Http
+import RemoteData
+import Route
+import Session
+import Style
+import TwoFactor
+import Url
+
+
+{-| setup is the secret being set up, recoveryCodes are shown once after turning it on or replacing them
+-}
+type alias Model =
+    { status : RemoteData.WebData TwoFactor.Status
+    , setup : Maybe TwoFactor.Setup
+    , code : String
+    , recoveryCodes : List String
+    , reply : Maybe String
+    , session : Session.Session
+    }
+
+
+type Msg
+    = GotStatus (RemoteData.WebData TwoFactor.Status)
+    | ClickedSetUp
+    | GotSetup (Result Http.Error TwoFactor.Setup)
+    | EnteredCode String
+    | SubmittedConfirm
+    | SubmittedRecoveryCodes
+    | ClickedDisable
+    | GotRecoveryCodes (Result Http.Error (List String))
+    | Disabled (Result Http.Error String)
+    | ClickedDone
+
+
+init : Session.Session -> ( Model, Cmd Msg )
+init session =
+    { status = RemoteData.Loading
+    , setup = Nothing
+    , code = ""
+    , recoveryCodes = []
+    , reply = Nothing
+    , session = session
+    }
+        |> withCmd (Api.getTwoFactor GotStatus)
+
+
+update : Msg -> Model -> ( Model, Cmd Msg )
+update msg model =
+    case msg of
+        GotStatus status ->
+            { model | status = status } |> withNoCmd
+
+        ClickedSetUp ->
+            { model | reply = Nothing } |> withCmd (Api.startTwoFactor GotSetup)
+
+        GotSetup (Ok setup) ->
+            { model | setup = Just setup, code = "" } |> withNoCmd
+
+        GotSetup (Err _) ->
+            { model | reply = Just "Could not start the setup, please try again later." } |> withNoCmd
+
+        EnteredCode s ->
+            { model | code = s } |> withNoCmd
+
+        SubmittedConfirm ->
+            { model | reply = Nothing } |> withCmd (Api.confirmTwoFactor model.code GotRecoveryCodes)
+
+        SubmittedRecoveryCodes ->
+            { model | reply = Nothing } |> withCmd (Api.resetRecoveryCodes model.code GotRecoveryCodes)
+
+        ClickedDisable ->
+            { model | reply = Nothing } |> withCmd (Api.disableTwoFactor model.code Disabled)
+
+        GotRecoveryCodes (Ok codes) ->
+            { model | recoveryCodes = codes, setup = Nothing, code = "" } |> withNoCmd
+
+        GotRecoveryCodes (Err e) ->
+            { model | reply = Just (errorReply e) } |> withNoCmd
+
+        Disabled (Ok _) ->
+            { model | code = "" } |> withCmd (Api.getTwoFactor GotStatus)
+
+        Disabled (Err e) ->
+            { model | reply = Just (errorReply e) } |> withNoCmd
+
+        -- reload, so the page gets roles that were held back until two-factor authentication was set up
+        ClickedDone ->
+            model |> withCmd (Route.load Route.Home)
+
+
+errorReply : Http.Error -> String
+errorReply e =
+    case e of
+        Http.BadStatus 403 ->
+            "Wrong code."
+
+        Http.BadStatus 410 ->
+            "The setup has expired. Please start again."
+
+        Http.BadStatus 429 ->
+            "Too many attempts. Please wait a while and try again."
+
+        _ ->
+            "Something went wrong, please try again later."
+
+
+view : Model -> { title : String, content : Html Msg }
+view model =
+    { title = "Two-factor authentication"
+    , content =
+        main_ [ id "content", class "container" ] <|
+            case ( model.recoveryCodes, model.status ) of
+                ( _ :: _, _ ) ->
+                    viewRecoveryCodes model.recoveryCodes
+
+                ( [], RemoteData.Success status ) ->
+                    if status.enabled then
+                        viewEnabled model status
+
+                    else
+                        viewDisabled model status
+
+                ( [], RemoteData.Failure _ ) ->
+                    [ text "Failed" |> Style.bodyAlert ]
+
+                _ ->
+                    [ Style.loadingIcon ]
+    }
+
+
+viewDisabled : Model -> TwoFactor.Status -> List (Html Msg)
+viewDisabled model status =
+    [ (if status.required then
+        "Your role requires two-factor authentication. Please set it up to continue."
+
+       else
+        "Two-factor authentication is off. With it, signing in also needs a code from an app on your phone."
+      )
+        |> text
+        |> Style.bodyAlert
+    , case model.setup of
+        Nothing ->
+            Html.div
+                [ class "w-full max-w-xs container fade-in" ]
+                [ Html.div
+                    [ class "bg-white shadow-md rounded px-8 pt-6 pb-8 m-4" ]
+                    [ Style.formButtonNoSubmit "Set up" [ Html.Events.onClick ClickedSetUp ]
+                    , viewReply model.reply
+                    ]
+                ]
+
+        Just setup ->
+            viewSetup model setup
+    ]
+
+
+viewSetup : Model -> TwoFactor.Setup -> Html Msg
+viewSetup model setup =
+    Html.div
+        [ class "w-full max-w-xs container fade-in" ]
+        [ Html.form
+            [ Html.Events.onSubmit SubmittedConfirm
+            , class "bg-white shadow-md rounded px-8 pt-6 pb-8 m-4"
+            ]
+            [ p [ class "text-sm mb-4" ] [ text "Scan this with your authenticator app, or enter the key by hand." ]
+            , case setup.qr of
+                Just svg ->
+                    img [ Html.Attributes.src ("data:image/svg+xml," ++ Url.percentEncode svg), class "mx-auto mb-4" ] []
+
+                Nothing ->
+                    Html.div [] []
+            , p [ class "text-xs font-mono break-all mb-4" ] [ text setup.secret ]
+            , Style.formInputField "Code from the app"
+                Nothing
+                [ Html.Events.onInput EnteredCode
+                , Html.Attributes.value model.code
+                , Html.Attributes.attribute "autocomplete" "one-time-code"
+                ]
+            , Style.formButton "Turn on" []
+            , viewReply model.reply
+            ]
+        ]
+
+
+viewEnabled : Model -> TwoFactor.Status -> List (Html Msg)
+viewEnabled model status =
+    [ "Two-factor authentication is on. You have "
+        ++ String.fromInt status.recoveryCodes
+        ++ " unused recovery codes."
+        |> text
+        |> Style.bodyAlert
+    , Html.div
+        [ class "w-full max-w-xs container fade-in" ]
+        [ Html.form
+            [ Html.Events.onSubmit SubmittedRecoveryCodes
+            , class "bg-white shadow-md rounded px-8 pt-6 pb-8 m-4"
+            ]
+            [ Style.formInputField "Code from your authenticator app"
+                Nothing
+                [ Html.Events.onInput EnteredCode
+                , Html.Attributes.value model.code
+                , Html.Attributes.attribute "autocomplete" "one-time-code"
+                ]
+            , Style.formButton "New recovery codes" []
+            , if status.required then
+                Html.div [] []
+
+              else
+                Style.formButtonNoSubmit "Turn off" [ Html.Events.onClick ClickedDisable ]
+            , viewReply model.reply
+            ]
+        ]
+    ]
+
+
+viewRecoveryCodes : List String -> List (Html Msg)
+viewRecoveryCodes codes =
+    [ text "Save these recovery codes somewhere safe. Each one can be used once to sign in without your phone, and they won't be shown again."
+        |> Style.bodyAlert
+    , Html.div
+        [ class "w-full max-w-xs container fade-in" ]
+        [ Html.div
+            [ class "bg-white shadow-md rounded px-8 pt-6 pb-8 m-4" ]
+            [ ul [ class "font-mono text-center mb-4" ] (List.map (\code -> li [] [ text code ]) codes)
+            , Style.formButtonNoSubmit "Done" [ Html.Events.onClick ClickedDone ]
+            ]
+        ]
+    ]
+
+
+viewReply : Maybe String -> Html msg
+viewReply reply =
+    case reply of
+        Just s ->
+            Html.div [ class "text-sm text-red-500 italic" ] [ text s ]
+
+        Nothing ->
+            Html.div [] []
+
+
+subscriptions : Model -> Sub Msg
+subscriptions model =
+    Sub.none
@@ -60,7 +60,7 @@ update msg model =
                         (Time.millisToPosix 0)
                         model.body
                         model.summary
-                        (Session.getUsernameUnsafe model.session)
+                        [ Article.Byline (Session.getUsernameUnsafe model.session) Nothing Nothing ]
                         model.image
             in
             { model | article = Just articleSummary } |> withNoCmd
@@ -15,6 +15,11 @@ type Route
     | Login
     | Register
     | Article Int
+    | Author String
+    | Confirmation String
+    | Invitation String
+    | ResetPassword String
+    | TwoFactor
     | WriteArticle UUID
     | Empty
 
@@ -37,6 +42,11 @@ parser =
         , Parser.map Login (s "login")
         , Parser.map Register (s "register")
         , Parser.map Article (s "article" </> int)
+        , Parser.map Author (s "author" </> string)
+        , Parser.map Confirmation (s "confirmation" </> string)
+        , Parser.map Invitation (s "invitation" </> string)
+        , Parser.map ResetPassword (s "reset_password" </> string)
+        , Parser.map TwoFactor (s "two_factor")
         , Parser.map WriteArticle (s "write_article" </> uuid)
         ]
 
@@ -79,6 +89,21 @@ routeToString route =
         Article id ->
             "/article/" ++ String.fromInt id
 
+        Author username ->
+            "/author/" ++ username
+
+        Confirmation result ->
+            "/confirmation/" ++ result
+
+        Invitation token ->
+            "/invitation/" ++ token
+
+        ResetPassword token ->
+            "/reset_password/" ++ token
+
+        TwoFactor ->
+            "/two_factor"
+
         WriteArticle uuid_ ->
             "/write_article/" ++ uuid_
 
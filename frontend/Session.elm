@@ -16,10 +16,11 @@ module Session exposing
     , init
     , loggedIn
     , logout
+    , needsTwoFactor
     )
 
 import Browser.Navigation exposing (Key)
-import Json.Decode exposing (Decoder, field, int, list, string)
+import Json.Decode exposing (Decoder, bool, field, int, list, string)
 import Localization exposing (Language)
 import Maybe
 
@@ -32,9 +33,13 @@ type Session
     = Session Key Language (Maybe Credentials) MenuStatus
 
 
+{-| needsTwoFactor is set when one of the roles requires two-factor authentication the user hasn't set up,
+until then the server refuses everything else
+-}
 type alias Credentials =
     { username : String
     , roles : List Role
+    , needsTwoFactor : Bool
     }
 
 
@@ -73,9 +78,10 @@ makeCredentials credentials =
 
 credentialsDecoder : Decoder Credentials
 credentialsDecoder =
-    Json.Decode.map2 Credentials
+    Json.Decode.map3 Credentials
         (field "username" string)
         (field "roles" (list role))
+        (Json.Decode.oneOf [ field "needs_two_factor" bool, Json.Decode.succeed False ])
 
 
 role : Decoder Role
@@ -152,6 +158,11 @@ isAdmin =
     hasRole Admin
 
 
+needsTwoFactor : Session -> Bool
+needsTwoFactor (Session _ _ credentials _) =
+    Maybe.map .needsTwoFactor credentials |> Maybe.withDefault False
+
+
 logout : Session -> Session
 logout (Session key lang _ open) =
     Session key lang (makeCredentials Nothing) open
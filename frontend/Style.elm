@@ -10,6 +10,7 @@ module Style exposing
     , linkAlert
     , loadingIcon
     , maybeBackgroundImage
+    , responsiveImage
     )
 
 import Html exposing (..)
@@ -183,6 +184,42 @@ maybeBackgroundImage filename =
     Maybe.withDefault "/image/placeholder.jpg" filename |> backgroundImage
 
 
+{-| An image that lets the browser pick a resized copy from the server.
+Data URLs and absolute paths (e.g. an unsaved preview) are used as they are.
+-}
+responsiveImage : String -> Maybe String -> List (Attribute msg) -> Html msg
+responsiveImage sizes filename attributes =
+    case filename of
+        Just name ->
+            if String.startsWith "data:" name || String.startsWith "/" name then
+                img (src name :: attributes) []
+
+            else
+                img
+                    ([ src <| imageUrl name 640
+                     , attribute "srcset" <| String.join ", " <| List.map (\w -> imageUrl name w ++ " " ++ String.fromInt w ++ "w") imageWidths
+                     , attribute "sizes" sizes
+                     ]
+                        ++ attributes
+                    )
+                    []
+
+        Nothing ->
+            img (src "/image/placeholder.jpg" :: attributes) []
+
+
+{-| Must match the widths the server allows.
+-}
+imageWidths : List Int
+imageWidths =
+    [ 320, 640, 960, 1280, 1920 ]
+
+
+imageUrl : String -> Int -> String
+imageUrl filename width =
+    "/image/" ++ filename ++ "?w=" ++ String.fromInt width
+
+
 divider : Html msg
 divider =
     Html.div [ class "w-full h-px divider my-4" ] []
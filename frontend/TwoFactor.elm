@@ -0,0 +1,42 @@
+module TwoFactor exposing (Setup, Status, recoveryCodesDecoder, setupDecoder, statusDecoder)
+
+import Json.Decode exposing (Decoder, bool, field, int, list, nullable, string)
+
+
+{-| required is set when one of the user's roles needs two-factor authentication
+-}
+type alias Status =
+    { enabled : Bool
+    , required : Bool
+    , recoveryCodes : Int
+    }
+
+
+{-| qr is an svg image of uri for the authenticator app to scan, secret is for typing in by hand
+-}
+type alias Setup =
+    { secret : String
+    , uri : String
+    , qr : Maybe String
+    }
+
+
+statusDecoder : Decoder Status
+statusDecoder =
+    Json.Decode.map3 Status
+        (field "enabled" bool)
+        (field "required" bool)
+        (field "recovery_codes" int)
+
+
+setupDecoder : Decoder Setup
+setupDecoder =
+    Json.Decode.map3 Setup
+        (field "secret" string)
+        (field "uri" string)
+        (field "qr" (nullable string))
+
+
+recoveryCodesDecoder : Decoder (List String)
+recoveryCodesDecoder =
+    field "codes" (list string)
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS article_revisions (
+	id SERIAL PRIMARY KEY,
+	article INTEGER NOT NULL REFERENCES articles(id),
+	headlineCN TEXT NOT NULL,
+	abstract TEXT NOT NULL,
+	articleBody TEXT NOT NULL,
+	wordCount INTEGER NOT NULL,
+	image TEXT,
+	editor INTEGER REFERENCES users(id),
+	dateCreated TIMESTAMP NOT NULL
+);
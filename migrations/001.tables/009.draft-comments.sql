@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS draft_comments (
+	id SERIAL PRIMARY KEY,
+	draft UUID NOT NULL REFERENCES temp_articles(id) ON DELETE CASCADE,
+	parent INTEGER REFERENCES draft_comments(id) ON DELETE CASCADE,
+	author INTEGER NOT NULL REFERENCES users(id),
+	body TEXT NOT NULL,
+	anchorStart INTEGER,
+	anchorEnd INTEGER,
+	anchorText TEXT,
+	anchorPrefix TEXT,
+	anchorSuffix TEXT,
+	resolved BOOLEAN NOT NULL DEFAULT FALSE,
+	resolvedBy INTEGER REFERENCES users(id),
+	dateResolved TIMESTAMP,
+	dateCreated TIMESTAMP NOT NULL
+);
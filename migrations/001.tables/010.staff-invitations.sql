@@ -0,0 +1,14 @@
+-- invitations created by an admin for a new staff member, separate from the
+-- invitations table which only confirms self-registered accounts
+-- tokenHash is a sha256 hash of the emailed token
+CREATE TABLE IF NOT EXISTS staff_invitations (
+	id SERIAL PRIMARY KEY,
+	email TEXT NOT NULL,
+	tokenHash TEXT UNIQUE NOT NULL,
+	roles INTEGER[] NOT NULL,
+	invitedBy INTEGER REFERENCES users(id) NOT NULL,
+	dateCreated TIMESTAMP NOT NULL,
+	dateExpires TIMESTAMP NOT NULL,
+	acceptedBy INTEGER REFERENCES users(id),
+	dateAccepted TIMESTAMP
+);
@@ -0,0 +1,22 @@
+-- contributors credited on articles who don't have an account
+CREATE TABLE IF NOT EXISTS guest_authors (
+	id SERIAL PRIMARY KEY,
+	name TEXT NOT NULL,
+	bio TEXT,
+	dateCreated TIMESTAMP NOT NULL
+);
+
+-- credited authors of an article in display order, each either a user or a guest
+CREATE TABLE IF NOT EXISTS article_bylines (
+	article INTEGER REFERENCES articles(id) ON DELETE CASCADE NOT NULL,
+	position SMALLINT NOT NULL,
+	userId INTEGER REFERENCES users(id),
+	guest INTEGER REFERENCES guest_authors(id),
+	PRIMARY KEY (article, position),
+	CHECK ((userId IS NULL) <> (guest IS NULL))
+);
+
+-- articles from before bylines are credited to their author
+INSERT INTO article_bylines(article, position, userId)
+	SELECT id, 0, author FROM articles
+	WHERE NOT EXISTS(SELECT 1 FROM article_bylines WHERE article_bylines.article = articles.id);
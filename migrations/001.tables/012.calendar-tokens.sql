@@ -0,0 +1,7 @@
+-- the secret in a user's calendar feed url, calendar apps can't send the auth cookie
+-- tokenHash is a sha256 hash of the token, which is only shown when it is issued
+CREATE TABLE IF NOT EXISTS calendar_tokens (
+	userId INTEGER PRIMARY KEY REFERENCES users(id),
+	tokenHash TEXT UNIQUE NOT NULL,
+	dateCreated TIMESTAMP NOT NULL
+);
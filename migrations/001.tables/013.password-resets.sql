@@ -0,0 +1,9 @@
+-- only a hash of the token is stored, the token itself is only ever in the email
+CREATE TABLE IF NOT EXISTS password_resets (
+	id SERIAL PRIMARY KEY,
+	userId INTEGER REFERENCES users(id) NOT NULL,
+	tokenHash TEXT UNIQUE NOT NULL,
+	dateCreated TIMESTAMP NOT NULL,
+	dateExpires TIMESTAMP NOT NULL,
+	dateUsed TIMESTAMP
+);
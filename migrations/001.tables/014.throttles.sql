@@ -0,0 +1,10 @@
+-- recent failures per (scope, key), e.g. ('login_user', username) or ('login_ip', ip)
+CREATE TABLE IF NOT EXISTS throttles (
+	scope TEXT NOT NULL,
+	key TEXT NOT NULL,
+	failures INTEGER NOT NULL,
+	lastFailure TIMESTAMP NOT NULL,
+	blockedUntil TIMESTAMP,
+	PRIMARY KEY (scope, key)
+);
+CREATE INDEX IF NOT EXISTS throttles_last_failure_idx ON throttles(lastFailure);
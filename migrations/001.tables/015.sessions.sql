@@ -0,0 +1,13 @@
+-- one row per logged in browser, the auth cookie holds the token and only its hash is stored
+CREATE TABLE IF NOT EXISTS sessions (
+	id SERIAL PRIMARY KEY,
+	userId INTEGER REFERENCES users(id) NOT NULL,
+	tokenHash TEXT UNIQUE NOT NULL,
+	dateCreated TIMESTAMP NOT NULL,
+	dateExpires TIMESTAMP NOT NULL,
+	lastSeen TIMESTAMP NOT NULL,
+	ip TEXT,
+	userAgent TEXT
+);
+
+CREATE INDEX IF NOT EXISTS sessions_user_idx ON sessions(userId);
@@ -0,0 +1,9 @@
+-- TOTP secret per user, it is only used for logging in once the user has confirmed a code from their app
+CREATE TABLE IF NOT EXISTS two_factor (
+	userId INTEGER PRIMARY KEY REFERENCES users(id),
+	secret BYTEA NOT NULL,
+	dateCreated TIMESTAMP NOT NULL,
+	dateConfirmed TIMESTAMP,
+	-- the time step of the last accepted code, so a code can't be used twice
+	lastStep BIGINT NOT NULL DEFAULT 0
+);
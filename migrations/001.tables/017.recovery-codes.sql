@@ -0,0 +1,9 @@
+-- single-use codes for logging in without the authenticator app, only hashes are stored
+CREATE TABLE IF NOT EXISTS recovery_codes (
+	id SERIAL PRIMARY KEY,
+	userId INTEGER REFERENCES users(id) NOT NULL,
+	codeHash TEXT NOT NULL,
+	dateUsed TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS recovery_codes_user_idx ON recovery_codes(userId);
@@ -0,0 +1,2 @@
+ALTER TABLE articles ALTER COLUMN wordCount TYPE INTEGER;
+ALTER TABLE temp_articles ALTER COLUMN wordCount TYPE INTEGER;
@@ -0,0 +1,9 @@
+-- dateLive is when the publish event was recorded, articles already visible count as live
+DO $$
+BEGIN
+	IF NOT EXISTS(SELECT 1 FROM information_schema.columns WHERE table_name='articles' AND column_name='datelive') THEN
+		ALTER TABLE articles ADD COLUMN dateLive TIMESTAMP;
+		UPDATE articles SET dateLive = coalesce(datePublished, dateCreated);
+	END IF;
+END
+$$;
@@ -0,0 +1,3 @@
+-- workflow: draft -> submitted -> in_review -> approved | changes_requested -> published
+ALTER TABLE temp_articles ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'draft'
+	CHECK (status IN ('draft', 'submitted', 'in_review', 'changes_requested', 'approved'));
@@ -0,0 +1,6 @@
+-- structured context for an entry, e.g. the ip and user agent of a login attempt
+ALTER TABLE logs ADD COLUMN IF NOT EXISTS detail JSONB;
+
+-- the audit log is read newest first, filtered by subject or user
+CREATE INDEX IF NOT EXISTS logs_subject_id_idx ON logs(subject, id);
+CREATE INDEX IF NOT EXISTS logs_userid_id_idx ON logs(userId, id);
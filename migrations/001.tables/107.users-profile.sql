@@ -0,0 +1,3 @@
+-- shown on the author's profile page, avatar is an uploaded image filename
+ALTER TABLE users ADD COLUMN IF NOT EXISTS bio TEXT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS avatar TEXT;
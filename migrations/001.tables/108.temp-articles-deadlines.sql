@@ -0,0 +1,3 @@
+-- when the author should submit the draft, and when its review should be finished
+ALTER TABLE temp_articles ADD COLUMN IF NOT EXISTS dateDue TIMESTAMP;
+ALTER TABLE temp_articles ADD COLUMN IF NOT EXISTS dateReviewDue TIMESTAMP;
@@ -0,0 +1,8 @@
+-- invitation now holds a sha256 hash of the emailed token, which expires a day after dateCreated
+ALTER TABLE invitations ADD COLUMN IF NOT EXISTS dateCreated TIMESTAMP;
+
+-- tokens sent before this are hashed in place and get a fresh day
+UPDATE invitations SET invitation = encode(digest(invitation, 'sha256'), 'hex'), dateCreated = timezone('utc', now())
+	WHERE dateCreated IS NULL;
+
+ALTER TABLE invitations ALTER COLUMN dateCreated SET NOT NULL;
@@ -0,0 +1,2 @@
+-- set while a login waits for the two-factor code, the session can't be used until then
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS pendingUntil TIMESTAMP;
@@ -0,0 +1,2 @@
+-- users with a role that requires it must set up two-factor authentication before doing anything else
+ALTER TABLE roles ADD COLUMN IF NOT EXISTS requireTwoFactor BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,5 @@
+-- how many of wordCount are Han or kana characters, so listings get reading times without the body
+-- NULL until the server has counted text saved before this column, see database::count_text
+ALTER TABLE articles ADD COLUMN IF NOT EXISTS cjkCharacters INTEGER;
+ALTER TABLE temp_articles ADD COLUMN IF NOT EXISTS cjkCharacters INTEGER;
+ALTER TABLE article_revisions ADD COLUMN IF NOT EXISTS cjkCharacters INTEGER;
@@ -1,7 +1,9 @@
+DROP FUNCTION IF EXISTS register;
 CREATE OR REPLACE FUNCTION register (
 	new_username TEXT,
 	pass TEXT,
-	confirm TEXT
+	confirm TEXT,
+	request JSONB DEFAULT NULL
 )
 RETURNS TABLE (
 	new_id INTEGER,
@@ -30,22 +32,22 @@ BEGIN
 	ELSE
 		-- hash the password 
 		SELECT crypt(pass, gen_salt('bf', 10)) into hashed_pw;
-		-- generate random token for invitation
-		SELECT substring(md5(random()::TEXT), 0, 36) INTO invitation_token;
+		-- generate random token for invitation, only its hash is stored
+		SELECT encode(gen_random_bytes(24), 'hex') INTO invitation_token;
 
 		-- insert into users table
 		INSERT INTO users(username, password, created, active)
-			VALUES (new_username, hashed_pw, now()::TIMESTAMP, FALSE) RETURNING id INTO new_id;
+			VALUES (new_username, hashed_pw, timezone('utc', now()), FALSE) RETURNING id INTO new_id;
 
 		SELECT 'Success', TRUE INTO message, success;
 
 		-- insert invitation token into table
-		INSERT INTO invitations(id, invitation)
-			VALUES (new_id, invitation_token);
+		INSERT INTO invitations(id, invitation, dateCreated)
+			VALUES (new_id, encode(digest(invitation_token, 'sha256'), 'hex'), timezone('utc', now()));
 
 		-- log the result
-		INSERT INTO logs(subject, userId, dateCreated, entry)
-			VALUES ('registration', new_id, now()::TIMESTAMP, 'Added new user');
+		INSERT INTO logs(subject, userId, dateCreated, entry, detail)
+			VALUES ('registration', new_id, timezone('utc', now()), 'Added new user', request);
 	END IF;
 
 	-- return the results table
@@ -1,3 +1,4 @@
+-- invitations expire a day after they are sent, resend_confirmation sends a new one
 CREATE OR REPLACE FUNCTION confirm (
 	invitation_token TEXT
 )
@@ -11,31 +12,41 @@ DECLARE
 	user_id INTEGER;
 	success BOOLEAN;
 	message TEXT;
+	token_hash TEXT;
+	sent TIMESTAMP;
 BEGIN
 	-- default to not approved
 	SELECT FALSE, '' INTO success, message;
+
+	SELECT encode(digest(invitation_token, 'sha256'), 'hex') INTO token_hash;
+	SELECT invitations.id, invitations.dateCreated INTO user_id, sent FROM invitations WHERE invitation = token_hash;
 	
 	-- if invitation doesn't exist
-	IF (SELECT NOT EXISTS(SELECT 1 FROM invitations WHERE invitation=invitation_token)) THEN
+	IF (user_id IS NULL) THEN
 		SELECT FALSE, 'Invitation does not exist' INTO success, message;
 
 		-- log the result
 		INSERT INTO logs(subject, userId, dateCreated, entry)
-			VALUES ('confirmation', null, now()::TIMESTAMP, 'Tried to confirm but invitation didn''t exist');
-	ELSE 
-		SELECT invitations.id INTO user_id FROM invitations WHERE invitation = invitation_token;
+			VALUES ('confirmation', null, timezone('utc', now()), 'Tried to confirm but invitation didn''t exist');
+
+	ELSIF (sent < timezone('utc', now()) - interval '1 day') THEN
+		SELECT FALSE, 'Invitation has expired' INTO success, message;
 
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('confirmation', user_id, timezone('utc', now()), 'Tried to confirm with an expired invitation');
+	ELSE 
 		-- activate the user 
 		UPDATE users SET active = TRUE WHERE id = user_id;
 
 		-- remove invitation now that it's confirmed
-		DELETE FROM invitations WHERE invitation = invitation_token;
+		DELETE FROM invitations WHERE id = user_id;
 
 		SELECT TRUE, 'User is activated' INTO success, message;
 
 		-- log the result
 		INSERT INTO logs(subject, userId, dateCreated, entry)
-			VALUES ('confirmation', user_id, now()::TIMESTAMP, 'Activated user');
+			VALUES ('confirmation', user_id, timezone('utc', now()), 'Activated user');
 
 	END IF;
 	
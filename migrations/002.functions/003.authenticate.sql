@@ -1,7 +1,8 @@
 DROP FUNCTION IF EXISTS authenticate;
 CREATE OR REPLACE FUNCTION authenticate (
 	usr TEXT,
-	pass TEXT
+	pass TEXT,
+	request JSONB DEFAULT NULL
 )
 RETURNS TABLE (
 	success BOOLEAN,
@@ -27,8 +28,8 @@ BEGIN
 		SELECT FALSE, 'Username does not exist' INTO success, message;
 
 		-- log the result
-		INSERT INTO logs(subject, userId, dateCreated, entry)
-			VALUES ('login', null, now()::TIMESTAMP, usr || ' does not exist');
+		INSERT INTO logs(subject, userId, dateCreated, entry, detail)
+			VALUES ('login', null, timezone('utc', now()), usr || ' does not exist', request);
 	ELSE
 		SELECT users.password, users.active, users.id FROM users WHERE username=usr INTO hashed_pw, active, user_id;
 	
@@ -37,8 +38,8 @@ BEGIN
 			SELECT FALSE, 'User is not activated' INTO success, message;
 
 			-- log the result
-			INSERT INTO logs(subject, userId, dateCreated, entry)
-				VALUES ('login', user_id, now()::TIMESTAMP, 'Tried to login before email confirmation');
+			INSERT INTO logs(subject, userId, dateCreated, entry, detail)
+				VALUES ('login', user_id, timezone('utc', now()), 'Tried to login before email confirmation', request);
 		-- hash password
 		ELSE
 			SELECT crypt(pass, hashed_pw) INTO validated_pw;
@@ -48,16 +49,16 @@ BEGIN
 				SELECT FALSE, 'Wrong password' INTO success, message;
 
 				-- log the result
-				INSERT INTO logs(subject, userId, dateCreated, entry)
-					VALUES ('login', user_id, now()::TIMESTAMP, 'Wrong password');
+				INSERT INTO logs(subject, userId, dateCreated, entry, detail)
+					VALUES ('login', user_id, timezone('utc', now()), 'Wrong password', request);
 			-- everything is correct
 			ELSE 
 				SELECT TRUE, 'Success' INTO success, message;
 				SELECT check_roles(usr) INTO roles;
 
 				-- log the result
-				INSERT INTO logs(subject, userId, dateCreated, entry)
-					VALUES ('login', user_id, now()::TIMESTAMP, 'Logged in');
+				INSERT INTO logs(subject, userId, dateCreated, entry, detail)
+					VALUES ('login', user_id, timezone('utc', now()), 'Logged in', request);
 			END IF;
 		END IF;
 	END IF;
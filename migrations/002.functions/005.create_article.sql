@@ -1,9 +1,14 @@
+DROP FUNCTION IF EXISTS create_article(INTEGER, TEXT, TEXT, TEXT, SMALLINT);
+DROP FUNCTION IF EXISTS create_article(TEXT, TEXT, TEXT, TEXT, SMALLINT);
+DROP FUNCTION IF EXISTS create_article(INTEGER, TEXT, TEXT, TEXT, INTEGER);
+DROP FUNCTION IF EXISTS create_article(TEXT, TEXT, TEXT, TEXT, INTEGER);
 CREATE OR REPLACE FUNCTION create_article (
 	usr INTEGER,
 	headline TEXT,
 	body TEXT,
 	title TEXT,
-	wordcount SMALLINT
+	wordcount INTEGER,
+	cjk INTEGER
 )
 RETURNS INTEGER
 AS
@@ -12,13 +17,21 @@ DECLARE
 	new_id INTEGER;
 BEGIN
 
-	INSERT INTO articles(headlineCN, dateCreated, disabled, articleBody, wordCount, abstract, author)
-	VALUES (title, now()::TIMESTAMP, FALSE, body, wordcount, title, usr)
+	INSERT INTO articles(headlineCN, dateCreated, disabled, articleBody, wordCount, cjkCharacters, abstract, author)
+	VALUES (headline, timezone('utc', now()), FALSE, body, wordcount, cjk, title, usr)
 	RETURNING id INTO new_id;
 
+	-- the first revision is the article as created
+	INSERT INTO article_revisions(article, headlineCN, abstract, articleBody, wordCount, cjkCharacters, editor, dateCreated)
+	VALUES (new_id, headline, title, body, wordcount, cjk, usr, timezone('utc', now()));
+
+	-- the creator is the first byline
+	INSERT INTO article_bylines(article, position, userId)
+	VALUES (new_id, 0, usr);
+
 	-- log the result
 	INSERT INTO logs(subject, userId, dateCreated, entry)
-		VALUES ('create_article', usr, now()::TIMESTAMP, 'Created new article: ' || cast(new_id as TEXT));
+		VALUES ('create_article', usr, timezone('utc', now()), 'Created new article: ' || cast(new_id as TEXT));
 
 	RETURN new_id;
 
@@ -30,7 +43,8 @@ CREATE OR REPLACE FUNCTION create_article (
 	headline TEXT,
 	body TEXT,
 	title TEXT,
-	wordcount SMALLINT
+	wordcount INTEGER,
+	cjk INTEGER
 )
 RETURNS INTEGER
 AS
@@ -42,13 +56,21 @@ BEGIN
 
 	SELECT users.id FROM users WHERE users.username=usr INTO usr_id;
 	
-	INSERT INTO articles(headlineCN, dateCreated, disabled, articleBody, wordCount, abstract, author)
-	VALUES (title, now()::TIMESTAMP, FALSE, body, wordcount, title, usr_id)
+	INSERT INTO articles(headlineCN, dateCreated, disabled, articleBody, wordCount, cjkCharacters, abstract, author)
+	VALUES (headline, timezone('utc', now()), FALSE, body, wordcount, cjk, title, usr_id)
 	RETURNING id INTO new_id;
 
+	-- the first revision is the article as created
+	INSERT INTO article_revisions(article, headlineCN, abstract, articleBody, wordCount, cjkCharacters, editor, dateCreated)
+	VALUES (new_id, headline, title, body, wordcount, cjk, usr_id, timezone('utc', now()));
+
+	-- the creator is the first byline
+	INSERT INTO article_bylines(article, position, userId)
+	VALUES (new_id, 0, usr_id);
+
 	-- log the result
 	INSERT INTO logs(subject, userId, dateCreated, entry)
-		VALUES ('create_article', usr_id, now()::TIMESTAMP, 'Created new article: ' || cast(new_id as TEXT));
+		VALUES ('create_article', usr_id, timezone('utc', now()), 'Created new article: ' || cast(new_id as TEXT));
 
 	RETURN new_id;
 
@@ -12,12 +12,12 @@ BEGIN
 	SELECT users.id FROM users WHERE username=usr INTO usr_id;
 
 	INSERT INTO temp_articles(dateCreated, author)
-	VALUES (now()::TIMESTAMP, usr_id)
+	VALUES (timezone('utc', now()), usr_id)
 	RETURNING id INTO new_id;
 
 	-- log the result
 	INSERT INTO logs(subject, userId, dateCreated, entry)
-		VALUES ('create_article', usr_id, now()::TIMESTAMP, 'Created new temp article: ' || cast(new_id as TEXT));
+		VALUES ('create_article', usr_id, timezone('utc', now()), 'Created new temp article: ' || cast(new_id as TEXT));
 
 	RETURN new_id;
 
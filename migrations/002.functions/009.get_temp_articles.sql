@@ -1,15 +1,17 @@
+DROP FUNCTION IF EXISTS get_temp_articles(TEXT);
 CREATE OR REPLACE FUNCTION get_temp_articles (
 	usr TEXT
 )
 RETURNS TABLE (
 	id UUID,
 	headlineCN TEXT,
-	dateCreated TIMESTAMP
+	dateCreated TIMESTAMP,
+	status TEXT
 )
 AS
 $$
 
-	SELECT temp_articles.id, headlineCN, dateCreated 
+	SELECT temp_articles.id, headlineCN, dateCreated, status
 	FROM temp_articles 
 	JOIN users
 	ON users.username = usr
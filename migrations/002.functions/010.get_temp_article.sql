@@ -0,0 +1,29 @@
+DROP FUNCTION IF EXISTS get_temp_article(TEXT, UUID);
+CREATE OR REPLACE FUNCTION get_temp_article (
+	usr TEXT,
+	draft UUID
+)
+RETURNS TABLE (
+	id UUID,
+	headlineCN TEXT,
+	abstract TEXT,
+	articleBody TEXT,
+	image TEXT,
+	dateCreated TIMESTAMP,
+	dateModified TIMESTAMP,
+	version INTEGER,
+	status TEXT
+)
+AS
+$$
+
+	SELECT temp_articles.id, headlineCN, abstract, articleBody, image, dateCreated, dateModified, version, status
+	FROM temp_articles
+	JOIN users
+	ON users.username = usr
+	WHERE temp_articles.id = draft
+	-- once submitted, reviewers and publishers can read the draft too
+	AND (author = users.id
+		OR (status <> 'draft' AND (authorize(usr, 1) OR authorize(usr, 3) OR authorize(usr, 4))));
+
+$$ LANGUAGE SQL;
@@ -0,0 +1,83 @@
+DROP FUNCTION IF EXISTS update_temp_article(TEXT, UUID, TEXT, TEXT, TEXT, TEXT);
+DROP FUNCTION IF EXISTS update_temp_article(TEXT, UUID, TEXT, TEXT, TEXT, TEXT, INTEGER);
+DROP FUNCTION IF EXISTS update_temp_article(TEXT, UUID, INTEGER, TEXT, TEXT, TEXT, TEXT, INTEGER);
+CREATE OR REPLACE FUNCTION update_temp_article (
+	usr TEXT,
+	draft UUID,
+	expected INTEGER,
+	headline TEXT,
+	summary TEXT,
+	body TEXT,
+	img TEXT,
+	words INTEGER,
+	cjk INTEGER
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT,
+	conflict BOOLEAN,
+	current_version INTEGER
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+	conflict BOOLEAN;
+	current_version INTEGER;
+	stored temp_articles%ROWTYPE;
+BEGIN
+	-- default to not updated
+	SELECT FALSE, '', FALSE, 0 INTO success, message, conflict, current_version;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	-- lock the row so two saves can't both pass the version check
+	SELECT * FROM temp_articles WHERE id=draft AND author=usr_id FOR UPDATE INTO stored;
+
+	-- fields left out of the save are kept, an empty image removes it
+	SELECT coalesce(headline, stored.headlineCN), coalesce(summary, stored.abstract), coalesce(body, stored.articleBody),
+		CASE WHEN img IS NULL THEN stored.image ELSE nullif(img, '') END
+		INTO headline, summary, body, img;
+	IF (body IS NOT DISTINCT FROM stored.articleBody) THEN
+		SELECT stored.wordCount, stored.cjkCharacters INTO words, cjk;
+	END IF;
+
+	-- only the author can update their own draft
+	IF (stored.id IS NULL) THEN
+		SELECT 'Draft does not exist' INTO message;
+
+	-- the author can't change a draft while it is with reviewers or publishers
+	ELSIF (stored.status NOT IN ('draft', 'changes_requested')) THEN
+		SELECT 'Draft is locked while in review' INTO message;
+
+	-- nothing changed, e.g. a repeated autosave, so don't bump the version
+	ELSIF (stored.headlineCN IS NOT DISTINCT FROM headline
+		AND stored.abstract IS NOT DISTINCT FROM summary
+		AND stored.articleBody IS NOT DISTINCT FROM body
+		AND stored.image IS NOT DISTINCT FROM img) THEN
+		SELECT TRUE, 'Draft saved', stored.version INTO success, message, current_version;
+
+	ELSIF (stored.version <> expected) THEN
+		SELECT 'Draft has been changed since it was loaded', TRUE, stored.version INTO message, conflict, current_version;
+
+	ELSE
+		UPDATE temp_articles
+		SET headlineCN = headline,
+			abstract = summary,
+			articleBody = body,
+			image = img,
+			wordCount = words,
+			cjkCharacters = cjk,
+			dateModified = timezone('utc', now()),
+			modifier = usr_id,
+			version = stored.version + 1
+		WHERE id = draft;
+
+		SELECT TRUE, 'Draft saved', stored.version + 1 INTO success, message, current_version;
+	END IF;
+
+	RETURN QUERY SELECT success, message, conflict, current_version;
+END;
+$$ LANGUAGE PLPGSQL;
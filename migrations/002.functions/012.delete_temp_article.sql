@@ -0,0 +1,44 @@
+CREATE OR REPLACE FUNCTION delete_temp_article (
+	usr TEXT,
+	draft UUID
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	stored_status TEXT;
+	success BOOLEAN;
+	message TEXT;
+BEGIN
+	-- default to not deleted
+	SELECT FALSE, '' INTO success, message;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	SELECT status FROM temp_articles WHERE id=draft AND author=usr_id FOR UPDATE INTO stored_status;
+
+	-- only the author can delete their own draft
+	IF (stored_status IS NULL) THEN
+		SELECT FALSE, 'Draft does not exist' INTO success, message;
+
+	-- reviewers and publishers are working on it, like update_temp_article
+	ELSIF (stored_status NOT IN ('draft', 'changes_requested')) THEN
+		SELECT FALSE, 'Draft is locked while in review' INTO success, message;
+
+	ELSE
+		DELETE FROM temp_articles WHERE id = draft;
+
+		SELECT TRUE, 'Draft deleted' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('delete_article', usr_id, timezone('utc', now()), 'Deleted temp article: ' || cast(draft as TEXT));
+	END IF;
+
+	RETURN QUERY SELECT success, message;
+END;
+$$ LANGUAGE PLPGSQL;
@@ -0,0 +1,75 @@
+-- publish_at schedules the article, it goes live straight away if that is missing or has passed
+DROP FUNCTION IF EXISTS publish_temp_article(TEXT, UUID);
+CREATE OR REPLACE FUNCTION publish_temp_article (
+	usr TEXT,
+	draft UUID,
+	publish_at TIMESTAMP DEFAULT NULL
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT,
+	article_id INTEGER
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+	article_id INTEGER;
+	draft_row temp_articles%ROWTYPE;
+BEGIN
+	-- default to not published
+	SELECT FALSE, '', 0 INTO success, message, article_id;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+	SELECT * FROM temp_articles WHERE id=draft FOR UPDATE INTO draft_row;
+
+	IF (draft_row.id IS NULL) THEN
+		SELECT 'Draft does not exist' INTO message;
+
+	-- only publishers and admins can publish, and only once a reviewer approved it
+	ELSIF (NOT authorize(usr, 4) AND NOT authorize(usr, 1)) THEN
+		SELECT 'Only publishers can publish a draft' INTO message;
+
+	ELSIF (draft_row.status <> 'approved') THEN
+		SELECT 'Draft has not been approved' INTO message;
+
+	ELSIF (coalesce(trim(draft_row.headlineCN), '') = '') THEN
+		SELECT 'Headline is required' INTO message;
+
+	ELSIF (coalesce(trim(draft_row.abstract), '') = '') THEN
+		SELECT 'Summary is required' INTO message;
+
+	ELSIF (coalesce(trim(draft_row.articleBody), '') = '') THEN
+		SELECT 'Article body is required' INTO message;
+
+	ELSE
+		SELECT create_article(draft_row.author, draft_row.headlineCN, draft_row.articleBody, draft_row.abstract, coalesce(draft_row.wordCount, 0), coalesce(draft_row.cjkCharacters, 0))
+			INTO article_id;
+
+		UPDATE articles
+		SET image = draft_row.image,
+			reviewer = draft_row.reviewer,
+			dateReviewed = draft_row.dateReviewed,
+			publisher = usr_id,
+			datePublished = coalesce(publish_at, timezone('utc', now())),
+			-- record_published_articles logs scheduled articles going live
+			dateLive = CASE WHEN publish_at > timezone('utc', now()) THEN NULL ELSE timezone('utc', now()) END
+		WHERE id = article_id;
+		UPDATE article_revisions SET image = draft_row.image WHERE article = article_id;
+
+		-- the draft now lives on as the article
+		DELETE FROM temp_articles WHERE id = draft;
+
+		SELECT TRUE, 'Article published' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('publish_article', usr_id, timezone('utc', now()), 'Published temp article ' || cast(draft as TEXT) || ' as article ' || cast(article_id as TEXT)
+				|| coalesce(', scheduled for ' || cast(publish_at as TEXT), ''));
+	END IF;
+
+	RETURN QUERY SELECT success, message, article_id;
+END;
+$$ LANGUAGE PLPGSQL;
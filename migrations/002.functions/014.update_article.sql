@@ -0,0 +1,70 @@
+DROP FUNCTION IF EXISTS update_article(TEXT, INTEGER, TEXT, TEXT, TEXT, TEXT, INTEGER);
+CREATE OR REPLACE FUNCTION update_article (
+	usr TEXT,
+	article_id INTEGER,
+	headline TEXT,
+	summary TEXT,
+	body TEXT,
+	img TEXT,
+	words INTEGER,
+	cjk INTEGER
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT,
+	revision_id INTEGER
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+	revision_id INTEGER;
+BEGIN
+	-- default to not updated
+	SELECT FALSE, '', 0 INTO success, message, revision_id;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	IF (SELECT NOT EXISTS(SELECT 1 FROM articles WHERE id=article_id)) THEN
+		SELECT 'Article does not exist' INTO message;
+
+	-- articles have been through review, so authors change them through a new draft,
+	-- only publishers and admins can correct them in place
+	ELSIF (NOT authorize(usr, 4) AND NOT authorize(usr, 1)) THEN
+		SELECT 'Only publishers can edit a published article' INTO message;
+
+	ELSE
+		-- articles created before revisions existed get their original version recorded first
+		IF (SELECT NOT EXISTS(SELECT 1 FROM article_revisions WHERE article=article_id)) THEN
+			INSERT INTO article_revisions(article, headlineCN, abstract, articleBody, wordCount, cjkCharacters, image, editor, dateCreated)
+				SELECT id, headlineCN, abstract, articleBody, wordCount, cjkCharacters, articles.image, author, coalesce(dateModified, dateCreated)
+				FROM articles WHERE id=article_id;
+		END IF;
+
+		UPDATE articles
+		SET headlineCN = headline,
+			abstract = summary,
+			articleBody = body,
+			image = img,
+			wordCount = words,
+			cjkCharacters = cjk,
+			dateModified = timezone('utc', now()),
+			modifier = usr_id
+		WHERE id = article_id;
+
+		INSERT INTO article_revisions(article, headlineCN, abstract, articleBody, wordCount, cjkCharacters, image, editor, dateCreated)
+			VALUES (article_id, headline, summary, body, words, cjk, img, usr_id, timezone('utc', now()))
+			RETURNING id INTO revision_id;
+
+		SELECT TRUE, 'Article saved' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('update_article', usr_id, timezone('utc', now()), 'Saved revision ' || cast(revision_id as TEXT) || ' of article ' || cast(article_id as TEXT));
+	END IF;
+
+	RETURN QUERY SELECT success, message, revision_id;
+END;
+$$ LANGUAGE PLPGSQL;
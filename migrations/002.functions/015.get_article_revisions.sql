@@ -0,0 +1,40 @@
+-- an article's history is for its author and the publishers and admins who look after it
+CREATE OR REPLACE FUNCTION can_read_revisions (
+	usr TEXT,
+	article_id INTEGER
+)
+RETURNS BOOLEAN
+AS
+$$
+
+	SELECT EXISTS(SELECT 1 FROM articles JOIN users ON users.id = articles.author
+		WHERE articles.id = article_id AND users.username = usr)
+		OR authorize(usr, 4) OR authorize(usr, 1);
+
+$$ LANGUAGE SQL;
+
+-- other authors get no revisions, the same as for an article that doesn't exist
+DROP FUNCTION IF EXISTS get_article_revisions(INTEGER);
+CREATE OR REPLACE FUNCTION get_article_revisions (
+	usr TEXT,
+	article_id INTEGER
+)
+RETURNS TABLE (
+	id INTEGER,
+	headlineCN TEXT,
+	wordCount INTEGER,
+	editor TEXT,
+	dateCreated TIMESTAMP
+)
+AS
+$$
+
+	SELECT article_revisions.id, headlineCN, wordCount, coalesce(users.display_name, users.username), article_revisions.dateCreated
+	FROM article_revisions
+	LEFT JOIN users
+	ON users.id = article_revisions.editor
+	WHERE article = article_id
+	AND can_read_revisions(usr, article_id)
+	ORDER BY article_revisions.id DESC;
+
+$$ LANGUAGE SQL;
@@ -0,0 +1,25 @@
+CREATE OR REPLACE FUNCTION restore_article_revision (
+	usr TEXT,
+	article_id INTEGER,
+	revision INTEGER
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT,
+	revision_id INTEGER
+)
+AS
+$$
+DECLARE
+	prev article_revisions%ROWTYPE;
+BEGIN
+	SELECT * FROM article_revisions WHERE id=revision AND article=article_id INTO prev;
+
+	IF (prev.id IS NULL) THEN
+		RETURN QUERY SELECT FALSE, 'Revision does not exist', 0;
+	ELSE
+		-- restoring saves the old version as a new revision, so nothing is lost
+		RETURN QUERY SELECT * FROM update_article(usr, article_id, prev.headlineCN, prev.abstract, prev.articleBody, prev.image, prev.wordCount, prev.cjkCharacters);
+	END IF;
+END;
+$$ LANGUAGE PLPGSQL;
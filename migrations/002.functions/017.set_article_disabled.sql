@@ -0,0 +1,47 @@
+CREATE OR REPLACE FUNCTION set_article_disabled (
+	usr TEXT,
+	article_id INTEGER,
+	disable BOOLEAN,
+	reason TEXT
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+BEGIN
+	-- default to not changed
+	SELECT FALSE, '' INTO success, message;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	IF (SELECT NOT EXISTS(SELECT 1 FROM articles WHERE id=article_id)) THEN
+		SELECT 'Article does not exist' INTO message;
+
+	-- only publishers and admins can take articles down
+	ELSIF (NOT authorize(usr, 1) AND NOT authorize(usr, 4)) THEN
+		SELECT 'Not allowed to disable articles' INTO message;
+
+	ELSE
+		UPDATE articles SET disabled = disable WHERE id = article_id;
+
+		IF (disable) THEN
+			SELECT TRUE, 'Article disabled' INTO success, message;
+		ELSE
+			SELECT TRUE, 'Article enabled' INTO success, message;
+		END IF;
+
+		-- log the result, the reason is only recorded here
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES (CASE WHEN disable THEN 'disable_article' ELSE 'enable_article' END, usr_id, timezone('utc', now()),
+				message || ' ' || cast(article_id as TEXT) || ': ' || coalesce(reason, ''));
+	END IF;
+
+	RETURN QUERY SELECT success, message;
+END;
+$$ LANGUAGE PLPGSQL;
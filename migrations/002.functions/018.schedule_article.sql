@@ -0,0 +1,47 @@
+CREATE OR REPLACE FUNCTION schedule_article (
+	usr TEXT,
+	article_id INTEGER,
+	publish_at TIMESTAMP
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+BEGIN
+	-- default to not scheduled
+	SELECT FALSE, '' INTO success, message;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	IF (SELECT NOT EXISTS(SELECT 1 FROM articles WHERE id=article_id)) THEN
+		SELECT 'Article does not exist' INTO message;
+
+	-- only publishers and admins can schedule articles
+	ELSIF (NOT authorize(usr, 1) AND NOT authorize(usr, 4)) THEN
+		SELECT 'Not allowed to schedule articles' INTO message;
+
+	ELSE
+		-- moving a live article into the future takes it offline until then,
+		-- record_published_articles logs it going live again
+		UPDATE articles
+		SET datePublished = publish_at,
+			publisher = usr_id,
+			dateLive = CASE WHEN publish_at > timezone('utc', now()) THEN NULL ELSE dateLive END
+		WHERE id = article_id;
+
+		SELECT TRUE, 'Article scheduled' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('schedule_article', usr_id, timezone('utc', now()), 'Scheduled article ' || cast(article_id as TEXT) || ' for ' || cast(publish_at as TEXT));
+	END IF;
+
+	RETURN QUERY SELECT success, message;
+END;
+$$ LANGUAGE PLPGSQL;
@@ -0,0 +1,31 @@
+-- logs articles whose publish time has passed, returns when the next one is due
+CREATE OR REPLACE FUNCTION record_published_articles ()
+RETURNS TIMESTAMP
+AS
+$$
+DECLARE
+	live RECORD;
+	next_due TIMESTAMP;
+BEGIN
+	FOR live IN
+		UPDATE articles
+		SET dateLive = timezone('utc', now())
+		WHERE dateLive IS NULL
+		AND NOT disabled
+		AND coalesce(datePublished, dateCreated) <= timezone('utc', now())
+		RETURNING id, publisher, datePublished
+	LOOP
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('publish_article', live.publisher, timezone('utc', now()),
+				'Article ' || cast(live.id as TEXT) || ' went live, scheduled for ' || coalesce(cast(live.datePublished as TEXT), 'now'));
+	END LOOP;
+
+	SELECT min(datePublished) FROM articles
+	WHERE dateLive IS NULL
+	AND NOT disabled
+	AND datePublished > timezone('utc', now())
+	INTO next_due;
+
+	RETURN next_due;
+END;
+$$ LANGUAGE PLPGSQL;
@@ -0,0 +1,86 @@
+-- moves a draft through the review workflow, checking the actor's role for each step
+CREATE OR REPLACE FUNCTION transition_temp_article (
+	usr TEXT,
+	draft UUID,
+	action TEXT,
+	note TEXT
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT,
+	status TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+	new_status TEXT;
+	stored temp_articles%ROWTYPE;
+BEGIN
+	-- default to not moved
+	SELECT FALSE, '', NULL INTO success, message, new_status;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+	SELECT * FROM temp_articles WHERE id=draft FOR UPDATE INTO stored;
+
+	IF (stored.id IS NULL) THEN
+		SELECT 'Draft does not exist' INTO message;
+
+	ELSIF (action = 'submit') THEN
+		IF (stored.author <> usr_id) THEN
+			SELECT 'Only the author can submit a draft' INTO message;
+		ELSIF (stored.status NOT IN ('draft', 'changes_requested')) THEN
+			SELECT 'Draft has already been submitted' INTO message;
+		ELSE
+			SELECT 'submitted' INTO new_status;
+		END IF;
+
+	ELSIF (action = 'start_review') THEN
+		IF (NOT authorize(usr, 3) AND NOT authorize(usr, 1)) THEN
+			SELECT 'Only reviewers can review a draft' INTO message;
+		-- authors who are also reviewers can't sign off on their own work, unless they are admins
+		ELSIF (stored.author = usr_id AND NOT authorize(usr, 1)) THEN
+			SELECT 'Only someone other than the author can review a draft' INTO message;
+		ELSIF (stored.status <> 'submitted') THEN
+			SELECT 'Draft is not waiting for review' INTO message;
+		ELSE
+			SELECT 'in_review' INTO new_status;
+			UPDATE temp_articles SET reviewer = usr_id WHERE id = draft;
+		END IF;
+
+	ELSIF (action IN ('approve', 'request_changes')) THEN
+		IF (NOT authorize(usr, 3) AND NOT authorize(usr, 1)) THEN
+			SELECT 'Only reviewers can review a draft' INTO message;
+		ELSIF (stored.author = usr_id AND NOT authorize(usr, 1)) THEN
+			SELECT 'Only someone other than the author can review a draft' INTO message;
+		ELSIF (stored.status <> 'in_review') THEN
+			SELECT 'Draft is not in review' INTO message;
+		-- the review belongs to whoever started it, admins can step in
+		ELSIF (stored.reviewer <> usr_id AND NOT authorize(usr, 1)) THEN
+			SELECT 'Draft is being reviewed by someone else' INTO message;
+		ELSE
+			SELECT CASE WHEN action = 'approve' THEN 'approved' ELSE 'changes_requested' END INTO new_status;
+			UPDATE temp_articles SET reviewer = usr_id, dateReviewed = timezone('utc', now()) WHERE id = draft;
+		END IF;
+
+	ELSE
+		SELECT 'Unknown action' INTO message;
+	END IF;
+
+	IF (new_status IS NOT NULL) THEN
+		UPDATE temp_articles SET status = new_status WHERE id = draft;
+
+		SELECT TRUE, 'Draft is ' || replace(new_status, '_', ' ') INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('workflow', usr_id, timezone('utc', now()),
+				'Moved temp article ' || cast(draft as TEXT) || ' from ' || stored.status || ' to ' || new_status
+				|| coalesce(': ' || nullif(note, ''), ''));
+	END IF;
+
+	RETURN QUERY SELECT success, message, coalesce(new_status, stored.status);
+END;
+$$ LANGUAGE PLPGSQL;
@@ -0,0 +1,26 @@
+-- drafts waiting on the user in the given role: author, reviewer or publisher
+CREATE OR REPLACE FUNCTION get_workflow_queue (
+	usr TEXT,
+	queue TEXT
+)
+RETURNS TABLE (
+	id UUID,
+	headlineCN TEXT,
+	status TEXT,
+	author TEXT,
+	dateModified TIMESTAMP
+)
+AS
+$$
+
+	SELECT temp_articles.id, headlineCN, status, coalesce(authors.display_name, authors.username), coalesce(temp_articles.dateModified, temp_articles.dateCreated)
+	FROM temp_articles
+	JOIN users AS authors ON authors.id = temp_articles.author
+	JOIN users AS actor ON actor.username = usr
+	WHERE (queue = 'author' AND temp_articles.author = actor.id AND status IN ('draft', 'changes_requested'))
+	OR (queue = 'reviewer' AND (authorize(usr, 3) OR authorize(usr, 1))
+		AND (status = 'submitted' OR (status = 'in_review' AND temp_articles.reviewer = actor.id)))
+	OR (queue = 'publisher' AND (authorize(usr, 4) OR authorize(usr, 1)) AND status = 'approved')
+	ORDER BY coalesce(temp_articles.dateModified, temp_articles.dateCreated);
+
+$$ LANGUAGE SQL;
@@ -0,0 +1,138 @@
+-- the draft's author, and reviewers, publishers and admins once it has been submitted,
+-- can see and write comments (the same rule as get_temp_article)
+CREATE OR REPLACE FUNCTION draft_comment_access (
+	usr TEXT,
+	draft_id UUID
+)
+RETURNS BOOLEAN
+AS
+$$
+
+	SELECT EXISTS(
+		SELECT 1 FROM temp_articles
+		JOIN users ON users.username = usr
+		WHERE temp_articles.id = draft_id
+		AND (temp_articles.author = users.id
+			OR (temp_articles.status <> 'draft' AND (authorize(usr, 1) OR authorize(usr, 3) OR authorize(usr, 4))))
+	);
+
+$$ LANGUAGE SQL;
+
+CREATE OR REPLACE FUNCTION get_draft_comments (
+	usr TEXT,
+	draft_id UUID
+)
+RETURNS TABLE (
+	id INTEGER,
+	parent INTEGER,
+	author TEXT,
+	body TEXT,
+	anchorStart INTEGER,
+	anchorEnd INTEGER,
+	anchorText TEXT,
+	resolved BOOLEAN,
+	dateCreated TIMESTAMP
+)
+AS
+$$
+
+	SELECT draft_comments.id, parent, coalesce(users.display_name, users.username), body,
+		anchorStart, anchorEnd, anchorText, resolved, draft_comments.dateCreated
+	FROM draft_comments
+	JOIN users ON users.id = draft_comments.author
+	WHERE draft = draft_id
+	AND draft_comment_access(usr, draft_id)
+	ORDER BY draft_comments.id;
+
+$$ LANGUAGE SQL;
+
+CREATE OR REPLACE FUNCTION add_draft_comment (
+	usr TEXT,
+	draft_id UUID,
+	parent_id INTEGER,
+	comment TEXT,
+	anchor_start INTEGER,
+	anchor_end INTEGER,
+	anchor_text TEXT,
+	anchor_prefix TEXT,
+	anchor_suffix TEXT
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT,
+	comment_id INTEGER
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+	comment_id INTEGER;
+BEGIN
+	-- default to not added
+	SELECT FALSE, '', 0 INTO success, message, comment_id;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	IF (NOT draft_comment_access(usr, draft_id)) THEN
+		SELECT 'Draft does not exist' INTO message;
+
+	ELSIF (parent_id IS NOT NULL AND NOT EXISTS(SELECT 1 FROM draft_comments WHERE id=parent_id AND draft=draft_id)) THEN
+		SELECT 'Comment does not exist' INTO message;
+
+	ELSE
+		INSERT INTO draft_comments(draft, parent, author, body, anchorStart, anchorEnd, anchorText, anchorPrefix, anchorSuffix, dateCreated)
+			VALUES (draft_id, parent_id, usr_id, comment, anchor_start, anchor_end, anchor_text, anchor_prefix, anchor_suffix, timezone('utc', now()))
+			RETURNING id INTO comment_id;
+
+		SELECT TRUE, 'Comment added' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('comment', usr_id, timezone('utc', now()), 'Commented on temp article ' || cast(draft_id as TEXT));
+	END IF;
+
+	RETURN QUERY SELECT success, message, comment_id;
+END;
+$$ LANGUAGE PLPGSQL;
+
+CREATE OR REPLACE FUNCTION set_comment_resolved (
+	usr TEXT,
+	draft_id UUID,
+	comment_id INTEGER,
+	resolve BOOLEAN
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+BEGIN
+	-- default to not changed
+	SELECT FALSE, '' INTO success, message;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	IF (NOT draft_comment_access(usr, draft_id)
+		OR NOT EXISTS(SELECT 1 FROM draft_comments WHERE id=comment_id AND draft=draft_id)) THEN
+		SELECT 'Comment does not exist' INTO message;
+
+	ELSE
+		UPDATE draft_comments
+		SET resolved = resolve,
+			resolvedBy = CASE WHEN resolve THEN usr_id ELSE NULL END,
+			dateResolved = CASE WHEN resolve THEN timezone('utc', now()) ELSE NULL END
+		WHERE id = comment_id;
+
+		SELECT TRUE, CASE WHEN resolve THEN 'Comment resolved' ELSE 'Comment reopened' END INTO success, message;
+	END IF;
+
+	RETURN QUERY SELECT success, message;
+END;
+$$ LANGUAGE PLPGSQL;
@@ -0,0 +1,195 @@
+-- users whose username or display name contains search, everyone for an empty search
+-- returns nothing unless usr is an admin
+CREATE OR REPLACE FUNCTION get_users (
+	usr TEXT,
+	search TEXT
+)
+RETURNS TABLE (
+	id INTEGER,
+	username TEXT,
+	display_name TEXT,
+	active BOOLEAN,
+	created TIMESTAMP,
+	roles INTEGER[]
+)
+AS
+$$
+
+	SELECT users.id, users.username, users.display_name, users.active, users.created,
+		coalesce(array_agg(user_roles.role ORDER BY user_roles.role) FILTER (WHERE user_roles.role IS NOT NULL), ARRAY[]::INTEGER[])
+	FROM users
+	LEFT JOIN user_roles ON user_roles.id = users.id
+	WHERE authorize(usr, 1)
+	AND (strpos(lower(users.username), lower(search)) > 0
+		OR strpos(lower(coalesce(users.display_name, '')), lower(search)) > 0)
+	GROUP BY users.id
+	ORDER BY users.username;
+
+$$ LANGUAGE SQL;
+
+CREATE OR REPLACE FUNCTION get_user (
+	usr TEXT,
+	user_id INTEGER
+)
+RETURNS TABLE (
+	id INTEGER,
+	username TEXT,
+	display_name TEXT,
+	active BOOLEAN,
+	created TIMESTAMP,
+	roles INTEGER[]
+)
+AS
+$$
+
+	SELECT users.id, users.username, users.display_name, users.active, users.created,
+		coalesce(array_agg(user_roles.role ORDER BY user_roles.role) FILTER (WHERE user_roles.role IS NOT NULL), ARRAY[]::INTEGER[])
+	FROM users
+	LEFT JOIN user_roles ON user_roles.id = users.id
+	WHERE authorize(usr, 1)
+	AND users.id = user_id
+	GROUP BY users.id;
+
+$$ LANGUAGE SQL;
+
+CREATE OR REPLACE FUNCTION set_user_role (
+	usr TEXT,
+	user_id INTEGER,
+	rle INTEGER,
+	grant_role BOOLEAN
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+BEGIN
+	-- default to not changed
+	SELECT FALSE, '' INTO success, message;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	IF (NOT authorize(usr, 1)) THEN
+		SELECT 'Only admins can manage users' INTO message;
+
+	ELSIF (SELECT NOT EXISTS(SELECT 1 FROM users WHERE users.id=user_id)) THEN
+		SELECT 'User does not exist' INTO message;
+
+	ELSIF (SELECT NOT EXISTS(SELECT 1 FROM roles WHERE roles.id=rle)) THEN
+		SELECT 'Role does not exist' INTO message;
+
+	-- otherwise the last admin could lock everyone out
+	ELSIF (NOT grant_role AND rle = 1 AND user_id = usr_id) THEN
+		SELECT 'Admins cannot remove their own admin role' INTO message;
+
+	ELSE
+		IF (grant_role) THEN
+			INSERT INTO user_roles(id, role) VALUES (user_id, rle) ON CONFLICT DO NOTHING;
+		ELSE
+			DELETE FROM user_roles WHERE user_roles.id=user_id AND role=rle;
+		END IF;
+
+		SELECT TRUE, CASE WHEN grant_role THEN 'Role granted' ELSE 'Role revoked' END INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('user_roles', usr_id, timezone('utc', now()),
+				CASE WHEN grant_role THEN 'Granted role ' ELSE 'Revoked role ' END || cast(rle as TEXT)
+				|| CASE WHEN grant_role THEN ' to user ' ELSE ' from user ' END || cast(user_id as TEXT));
+	END IF;
+
+	RETURN QUERY SELECT success, message;
+END;
+$$ LANGUAGE PLPGSQL;
+
+CREATE OR REPLACE FUNCTION set_display_name (
+	usr TEXT,
+	user_id INTEGER,
+	name TEXT
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+BEGIN
+	-- default to not changed
+	SELECT FALSE, '' INTO success, message;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	IF (NOT authorize(usr, 1)) THEN
+		SELECT 'Only admins can manage users' INTO message;
+
+	ELSIF (SELECT NOT EXISTS(SELECT 1 FROM users WHERE users.id=user_id)) THEN
+		SELECT 'User does not exist' INTO message;
+
+	ELSE
+		-- an empty name falls back to the username
+		UPDATE users SET display_name = nullif(trim(name), '') WHERE users.id=user_id;
+
+		SELECT TRUE, 'Display name saved' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('display_name', usr_id, timezone('utc', now()), 'Set display name of user ' || cast(user_id as TEXT) || ' to ' || coalesce(nullif(trim(name), ''), 'NULL'));
+	END IF;
+
+	RETURN QUERY SELECT success, message;
+END;
+$$ LANGUAGE PLPGSQL;
+
+CREATE OR REPLACE FUNCTION set_user_active (
+	usr TEXT,
+	user_id INTEGER,
+	activate BOOLEAN
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+BEGIN
+	-- default to not changed
+	SELECT FALSE, '' INTO success, message;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	IF (NOT authorize(usr, 1)) THEN
+		SELECT 'Only admins can manage users' INTO message;
+
+	ELSIF (SELECT NOT EXISTS(SELECT 1 FROM users WHERE users.id=user_id)) THEN
+		SELECT 'User does not exist' INTO message;
+
+	ELSIF (NOT activate AND user_id = usr_id) THEN
+		SELECT 'Admins cannot deactivate themselves' INTO message;
+
+	ELSE
+		UPDATE users SET active = activate WHERE users.id=user_id;
+
+		SELECT TRUE, CASE WHEN activate THEN 'User reactivated' ELSE 'User deactivated' END INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('user_active', usr_id, timezone('utc', now()),
+				CASE WHEN activate THEN 'Reactivated user ' ELSE 'Deactivated user ' END || cast(user_id as TEXT));
+	END IF;
+
+	RETURN QUERY SELECT success, message;
+END;
+$$ LANGUAGE PLPGSQL;
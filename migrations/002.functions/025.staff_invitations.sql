@@ -0,0 +1,183 @@
+-- returns the token to email to the new staff member
+CREATE OR REPLACE FUNCTION create_staff_invitation (
+	usr TEXT,
+	invitee TEXT,
+	role_ids INTEGER[]
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT,
+	invitation_token TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+	invitation_token TEXT;
+BEGIN
+	-- default to not invited
+	SELECT FALSE, '', '' INTO success, message, invitation_token;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	IF (NOT authorize(usr, 1)) THEN
+		SELECT 'Only admins can invite staff' INTO message;
+
+	ELSIF (coalesce(trim(invitee), '') = '') THEN
+		SELECT 'An email address is required' INTO message;
+
+	ELSIF (coalesce(array_length(role_ids, 1), 0) = 0) THEN
+		SELECT 'At least one role is required' INTO message;
+
+	ELSIF (SELECT EXISTS(SELECT 1 FROM unnest(role_ids) AS r(id) WHERE r.id NOT IN (SELECT roles.id FROM roles))) THEN
+		SELECT 'Role does not exist' INTO message;
+
+	ELSIF (SELECT EXISTS(SELECT 1 FROM users WHERE username=trim(invitee))) THEN
+		SELECT 'Username already exists' INTO message;
+
+	ELSE
+		-- the token is all it takes to get these roles, so it comes from pgcrypto rather than random()
+		SELECT encode(gen_random_bytes(24), 'hex') INTO invitation_token;
+
+		-- only the newest invitation for an address can be accepted
+		DELETE FROM staff_invitations WHERE email=trim(invitee) AND acceptedBy IS NULL;
+
+		INSERT INTO staff_invitations(email, tokenHash, roles, invitedBy, dateCreated, dateExpires)
+			VALUES (trim(invitee), encode(digest(invitation_token, 'sha256'), 'hex'), ARRAY(SELECT DISTINCT unnest(role_ids) ORDER BY 1), usr_id,
+				timezone('utc', now()), timezone('utc', now()) + INTERVAL '7 days');
+
+		SELECT TRUE, 'Invitation created' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('staff_invitation', usr_id, timezone('utc', now()), 'Invited ' || trim(invitee) || ' with roles ' || cast(role_ids as TEXT));
+	END IF;
+
+	RETURN QUERY SELECT success, message, invitation_token;
+END;
+$$ LANGUAGE PLPGSQL;
+
+-- invitations that have not been accepted yet, including expired ones
+CREATE OR REPLACE FUNCTION get_staff_invitations (
+	usr TEXT
+)
+RETURNS TABLE (
+	id INTEGER,
+	email TEXT,
+	roles INTEGER[],
+	invitedBy TEXT,
+	dateCreated TIMESTAMP,
+	dateExpires TIMESTAMP
+)
+AS
+$$
+
+	SELECT staff_invitations.id, email, roles, users.username, dateCreated, dateExpires
+	FROM staff_invitations
+	JOIN users ON users.id = staff_invitations.invitedBy
+	WHERE authorize(usr, 1)
+	AND acceptedBy IS NULL
+	ORDER BY dateCreated DESC;
+
+$$ LANGUAGE SQL;
+
+CREATE OR REPLACE FUNCTION revoke_staff_invitation (
+	usr TEXT,
+	invitation_id INTEGER
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+BEGIN
+	-- default to not revoked
+	SELECT FALSE, '' INTO success, message;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	IF (NOT authorize(usr, 1)) THEN
+		SELECT 'Only admins can invite staff' INTO message;
+
+	ELSIF (SELECT NOT EXISTS(SELECT 1 FROM staff_invitations WHERE staff_invitations.id=invitation_id AND acceptedBy IS NULL)) THEN
+		SELECT 'Invitation does not exist' INTO message;
+
+	ELSE
+		DELETE FROM staff_invitations WHERE staff_invitations.id=invitation_id;
+
+		SELECT TRUE, 'Invitation revoked' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('staff_invitation', usr_id, timezone('utc', now()), 'Revoked staff invitation ' || cast(invitation_id as TEXT));
+	END IF;
+
+	RETURN QUERY SELECT success, message;
+END;
+$$ LANGUAGE PLPGSQL;
+
+-- creates the active account and its roles in one go, nothing is kept if any step fails
+CREATE OR REPLACE FUNCTION accept_staff_invitation (
+	invitation_token TEXT,
+	pass TEXT,
+	confirm TEXT
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT
+)
+AS
+$$
+DECLARE
+	invited staff_invitations%ROWTYPE;
+	new_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+BEGIN
+	-- default to not accepted
+	SELECT FALSE, '' INTO success, message;
+
+	-- lock the invitation so it cannot be accepted twice at once
+	SELECT * FROM staff_invitations WHERE tokenHash=encode(digest(invitation_token, 'sha256'), 'hex') AND acceptedBy IS NULL FOR UPDATE INTO invited;
+
+	IF (invited.id IS NULL) THEN
+		SELECT 'Invitation does not exist' INTO message;
+
+	ELSIF (invited.dateExpires < timezone('utc', now())) THEN
+		SELECT 'Invitation has expired' INTO message;
+
+	ELSIF (coalesce(pass, '') = '') THEN
+		SELECT 'A password is required' INTO message;
+
+	ELSIF (pass <> confirm) THEN
+		SELECT 'Password and confirmed password do not match' INTO message;
+
+	ELSIF (SELECT EXISTS(SELECT 1 FROM users WHERE username=invited.email)) THEN
+		SELECT 'Username already exists' INTO message;
+
+	ELSE
+		INSERT INTO users(username, password, created, active)
+			VALUES (invited.email, crypt(pass, gen_salt('bf', 10)), timezone('utc', now()), TRUE) RETURNING id INTO new_id;
+
+		INSERT INTO user_roles(id, role)
+			SELECT new_id, unnest(invited.roles);
+
+		UPDATE staff_invitations SET acceptedBy = new_id, dateAccepted = timezone('utc', now()) WHERE id = invited.id;
+
+		SELECT TRUE, 'User is activated' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('staff_invitation', new_id, timezone('utc', now()), 'Accepted staff invitation ' || cast(invited.id as TEXT) || ' from user ' || cast(invited.invitedBy as TEXT));
+	END IF;
+
+	RETURN QUERY SELECT success, message;
+END;
+$$ LANGUAGE PLPGSQL;
@@ -0,0 +1,40 @@
+-- newest first; pass the id of the last entry seen as after_id to get the next page
+-- every filter is optional, and nothing is returned unless usr is an admin
+CREATE OR REPLACE FUNCTION get_logs (
+	usr TEXT,
+	log_subject TEXT,
+	log_user TEXT,
+	since TIMESTAMP,
+	until TIMESTAMP,
+	search TEXT,
+	after_id INTEGER,
+	lim INTEGER
+)
+RETURNS TABLE (
+	id INTEGER,
+	subject TEXT,
+	userId INTEGER,
+	username TEXT,
+	dateCreated TIMESTAMP,
+	entry TEXT,
+	detail JSONB
+)
+AS
+$$
+
+	SELECT logs.id, logs.subject, logs.userId, users.username, logs.dateCreated, logs.entry, logs.detail
+	FROM logs
+	LEFT JOIN users ON users.id = logs.userId
+	WHERE authorize(usr, 1)
+	AND (log_subject IS NULL OR logs.subject = log_subject)
+	AND (log_user IS NULL OR users.username = log_user)
+	AND (since IS NULL OR logs.dateCreated >= since)
+	AND (until IS NULL OR logs.dateCreated < until)
+	AND (search IS NULL
+		OR strpos(lower(logs.entry), lower(search)) > 0
+		OR strpos(lower(logs.detail::TEXT), lower(search)) > 0)
+	AND (after_id IS NULL OR logs.id < after_id)
+	ORDER BY logs.id DESC
+	LIMIT lim;
+
+$$ LANGUAGE SQL;
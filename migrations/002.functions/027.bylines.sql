@@ -0,0 +1,173 @@
+-- bylines of an article in order, as [{name, bio, username}], username is null for guests
+CREATE OR REPLACE FUNCTION article_bylines (
+	article_id INTEGER
+)
+RETURNS JSONB
+AS
+$$
+
+	SELECT coalesce(jsonb_agg(jsonb_build_object(
+			'name', coalesce(users.display_name, users.username, guest_authors.name),
+			'bio', coalesce(users.bio, guest_authors.bio),
+			'username', users.username)
+		ORDER BY article_bylines.position), '[]'::JSONB)
+	FROM article_bylines
+	LEFT JOIN users ON users.id = article_bylines.userId
+	LEFT JOIN guest_authors ON guest_authors.id = article_bylines.guest
+	WHERE article_bylines.article = article_id;
+
+$$ LANGUAGE SQL;
+
+-- bylines is a JSON array of {"username": ...} or {"guest": id}, in display order
+-- unknown usernames come back with a null userId
+CREATE OR REPLACE FUNCTION parse_bylines (
+	bylines JSONB
+)
+RETURNS TABLE (
+	pos SMALLINT,
+	username TEXT,
+	userId INTEGER,
+	guest INTEGER
+)
+AS
+$$
+
+	SELECT (b.ordinality - 1)::SMALLINT, b.value->>'username', users.id, (b.value->>'guest')::INTEGER
+	FROM jsonb_array_elements(CASE WHEN jsonb_typeof(bylines) = 'array' THEN bylines ELSE '[]'::JSONB END)
+		WITH ORDINALITY AS b(value, ordinality)
+	LEFT JOIN users ON users.username = b.value->>'username';
+
+$$ LANGUAGE SQL;
+
+CREATE OR REPLACE FUNCTION set_article_bylines (
+	usr TEXT,
+	article_id INTEGER,
+	bylines JSONB
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+BEGIN
+	-- default to not changed
+	SELECT FALSE, '' INTO success, message;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	IF (SELECT NOT EXISTS(SELECT 1 FROM articles WHERE id=article_id)) THEN
+		SELECT 'Article does not exist' INTO message;
+
+	-- only the article's author or an admin can change who is credited
+	ELSIF (SELECT NOT EXISTS(SELECT 1 FROM articles WHERE id=article_id AND author=usr_id) AND NOT authorize(usr, 1)) THEN
+		SELECT 'Not allowed to edit this article' INTO message;
+
+	ELSIF (SELECT NOT EXISTS(SELECT 1 FROM parse_bylines(bylines) AS b)) THEN
+		SELECT 'At least one byline is required' INTO message;
+
+	ELSIF (SELECT EXISTS(SELECT 1 FROM parse_bylines(bylines) AS b WHERE (b.username IS NULL) = (b.guest IS NULL))) THEN
+		SELECT 'Each byline needs either a username or a guest' INTO message;
+
+	ELSIF (SELECT EXISTS(SELECT 1 FROM parse_bylines(bylines) AS b WHERE b.username IS NOT NULL AND b.userId IS NULL)) THEN
+		SELECT 'User does not exist' INTO message;
+
+	ELSIF (SELECT EXISTS(SELECT 1 FROM parse_bylines(bylines) AS b WHERE b.guest IS NOT NULL AND b.guest NOT IN (SELECT id FROM guest_authors))) THEN
+		SELECT 'Guest author does not exist' INTO message;
+
+	ELSIF (SELECT count(DISTINCT b.userId) + count(DISTINCT b.guest) < count(*) FROM parse_bylines(bylines) AS b) THEN
+		SELECT 'The same author is credited twice' INTO message;
+
+	ELSE
+		DELETE FROM article_bylines WHERE article=article_id;
+
+		INSERT INTO article_bylines(article, position, userId, guest)
+			SELECT article_id, b.pos, b.userId, b.guest FROM parse_bylines(bylines) AS b;
+
+		SELECT TRUE, 'Bylines saved' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry, detail)
+			VALUES ('bylines', usr_id, timezone('utc', now()), 'Set bylines of article ' || cast(article_id as TEXT), bylines);
+	END IF;
+
+	RETURN QUERY SELECT success, message;
+END;
+$$ LANGUAGE PLPGSQL;
+
+CREATE OR REPLACE FUNCTION get_guest_authors (
+	usr TEXT
+)
+RETURNS TABLE (
+	id INTEGER,
+	name TEXT,
+	bio TEXT
+)
+AS
+$$
+
+	SELECT guest_authors.id, guest_authors.name, guest_authors.bio
+	FROM guest_authors
+	WHERE authorize(usr, 1) OR authorize(usr, 2)
+	ORDER BY guest_authors.name;
+
+$$ LANGUAGE SQL;
+
+-- creates a guest author when guest_id is null, otherwise updates it
+CREATE OR REPLACE FUNCTION save_guest_author (
+	usr TEXT,
+	guest_id INTEGER,
+	guest_name TEXT,
+	guest_bio TEXT
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT,
+	saved_id INTEGER
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+	saved_id INTEGER;
+BEGIN
+	-- default to not saved
+	SELECT FALSE, '', 0 INTO success, message, saved_id;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	IF (NOT authorize(usr, 1) AND NOT authorize(usr, 2)) THEN
+		SELECT 'Only authors can edit guest authors' INTO message;
+
+	ELSIF (coalesce(trim(guest_name), '') = '') THEN
+		SELECT 'A name is required' INTO message;
+
+	ELSIF (guest_id IS NOT NULL AND NOT EXISTS(SELECT 1 FROM guest_authors WHERE id=guest_id)) THEN
+		SELECT 'Guest author does not exist' INTO message;
+
+	ELSE
+		IF (guest_id IS NULL) THEN
+			INSERT INTO guest_authors(name, bio, dateCreated)
+				VALUES (trim(guest_name), nullif(trim(guest_bio), ''), timezone('utc', now()))
+				RETURNING id INTO saved_id;
+		ELSE
+			UPDATE guest_authors SET name = trim(guest_name), bio = nullif(trim(guest_bio), '') WHERE id=guest_id;
+			SELECT guest_id INTO saved_id;
+		END IF;
+
+		SELECT TRUE, 'Guest author saved' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('guest_author', usr_id, timezone('utc', now()), 'Saved guest author ' || cast(saved_id as TEXT));
+	END IF;
+
+	RETURN QUERY SELECT success, message, saved_id;
+END;
+$$ LANGUAGE PLPGSQL;
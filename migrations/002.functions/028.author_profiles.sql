@@ -0,0 +1,95 @@
+-- anyone with the author role, or credited on an article, has a public profile
+CREATE OR REPLACE FUNCTION get_author_profile (
+	author_name TEXT
+)
+RETURNS TABLE (
+	username TEXT,
+	display_name TEXT,
+	bio TEXT,
+	avatar TEXT
+)
+AS
+$$
+
+	SELECT users.username, users.display_name, users.bio, users.avatar
+	FROM users
+	WHERE users.username = author_name
+	AND users.active
+	AND (EXISTS(SELECT 1 FROM user_roles WHERE user_roles.id = users.id AND user_roles.role = 2)
+		OR EXISTS(SELECT 1 FROM article_bylines WHERE article_bylines.userId = users.id));
+
+$$ LANGUAGE SQL;
+
+-- live articles crediting author_name, newest first
+-- pass the id of the last article seen as after_id to get the next page
+DROP FUNCTION IF EXISTS get_author_articles(TEXT, INTEGER, INTEGER);
+CREATE OR REPLACE FUNCTION get_author_articles (
+	author_name TEXT,
+	after_id INTEGER,
+	lim INTEGER
+)
+RETURNS TABLE (
+	id INTEGER,
+	headlineCN TEXT,
+	dateCreated TIMESTAMP,
+	wordCount INTEGER,
+	cjkCharacters INTEGER,
+	abstract TEXT,
+	bylines JSONB,
+	image TEXT
+)
+AS
+$$
+
+	SELECT articles.id, articles.headlineCN, articles.dateCreated, articles.wordCount, articles.cjkCharacters, articles.abstract,
+		article_bylines(articles.id), articles.image
+	FROM articles
+	WHERE NOT articles.disabled
+	AND coalesce(articles.datePublished, articles.dateCreated) <= timezone('utc', now())
+	AND EXISTS(SELECT 1 FROM article_bylines
+		JOIN users ON users.id = article_bylines.userId
+		WHERE article_bylines.article = articles.id AND users.username = author_name)
+	AND (after_id IS NULL OR articles.id < after_id)
+	ORDER BY articles.id DESC
+	LIMIT lim;
+
+$$ LANGUAGE SQL;
+
+-- authors edit their own profile, an empty bio or avatar clears it
+CREATE OR REPLACE FUNCTION set_author_profile (
+	usr TEXT,
+	new_bio TEXT,
+	new_avatar TEXT
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+BEGIN
+	-- default to not changed
+	SELECT FALSE, '' INTO success, message;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	IF (NOT authorize(usr, 2)) THEN
+		SELECT 'Only authors have a profile' INTO message;
+
+	ELSE
+		UPDATE users SET bio = nullif(trim(new_bio), ''), avatar = nullif(trim(new_avatar), '') WHERE users.id=usr_id;
+
+		SELECT TRUE, 'Profile saved' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('author_profile', usr_id, timezone('utc', now()), 'Updated author profile');
+	END IF;
+
+	RETURN QUERY SELECT success, message;
+END;
+$$ LANGUAGE PLPGSQL;
@@ -0,0 +1,178 @@
+-- publications, review deadlines and draft deadlines falling in [since, until), by date
+-- staff see every publication, reviews are shown to reviewers and publishers,
+-- draft deadlines to publishers, and authors always see their own drafts
+CREATE OR REPLACE FUNCTION get_calendar (
+	usr TEXT,
+	since TIMESTAMP,
+	until TIMESTAMP
+)
+RETURNS TABLE (
+	kind TEXT,
+	event_date TIMESTAMP,
+	article_id INTEGER,
+	draft UUID,
+	headline TEXT,
+	status TEXT,
+	person TEXT
+)
+AS
+$$
+
+	SELECT 'publication', coalesce(articles.datePublished, articles.dateCreated), articles.id, NULL::UUID, articles.headlineCN,
+		CASE WHEN coalesce(articles.datePublished, articles.dateCreated) <= timezone('utc', now()) THEN 'published' ELSE 'scheduled' END,
+		(SELECT string_agg(b.value->>'name', '、') FROM jsonb_array_elements(article_bylines(articles.id)) AS b)
+	FROM articles
+	WHERE EXISTS(SELECT 1 FROM user_roles JOIN users ON users.id = user_roles.id WHERE users.username = usr)
+	AND NOT articles.disabled
+	AND coalesce(articles.datePublished, articles.dateCreated) >= since
+	AND coalesce(articles.datePublished, articles.dateCreated) < until
+
+	UNION ALL
+
+	SELECT 'review', temp_articles.dateReviewDue, NULL, temp_articles.id, temp_articles.headlineCN, temp_articles.status,
+		coalesce(authors.display_name, authors.username)
+	FROM temp_articles
+	JOIN users AS authors ON authors.id = temp_articles.author
+	WHERE (authors.username = usr OR authorize(usr, 3) OR authorize(usr, 4) OR authorize(usr, 1))
+	AND temp_articles.status IN ('submitted', 'in_review')
+	AND temp_articles.dateReviewDue >= since AND temp_articles.dateReviewDue < until
+
+	UNION ALL
+
+	SELECT 'draft', temp_articles.dateDue, NULL, temp_articles.id, temp_articles.headlineCN, temp_articles.status,
+		coalesce(authors.display_name, authors.username)
+	FROM temp_articles
+	JOIN users AS authors ON authors.id = temp_articles.author
+	WHERE (authors.username = usr OR authorize(usr, 4) OR authorize(usr, 1))
+	AND temp_articles.status IN ('draft', 'changes_requested')
+	AND temp_articles.dateDue >= since AND temp_articles.dateDue < until
+
+	ORDER BY 2, 1;
+
+$$ LANGUAGE SQL;
+
+-- the author or a publisher sets when a draft is due and when its review is due, NULL clears a date
+CREATE OR REPLACE FUNCTION set_draft_deadlines (
+	usr TEXT,
+	draft UUID,
+	due TIMESTAMP,
+	review_due TIMESTAMP
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+	draft_author INTEGER;
+BEGIN
+	-- default to not changed
+	SELECT FALSE, '' INTO success, message;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+	SELECT author FROM temp_articles WHERE id=draft INTO draft_author;
+
+	IF (draft_author IS NULL) THEN
+		SELECT 'Draft does not exist' INTO message;
+
+	ELSIF (draft_author <> usr_id AND NOT authorize(usr, 4) AND NOT authorize(usr, 1)) THEN
+		SELECT 'Only the author or a publisher can set deadlines' INTO message;
+
+	ELSE
+		UPDATE temp_articles SET dateDue = due, dateReviewDue = review_due WHERE id = draft;
+
+		SELECT TRUE, 'Deadlines saved' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('deadlines', usr_id, timezone('utc', now()),
+				'Set deadlines of temp article ' || cast(draft as TEXT)
+				|| ' to ' || coalesce(cast(due as TEXT), 'none')
+				|| ', review ' || coalesce(cast(review_due as TEXT), 'none'));
+	END IF;
+
+	RETURN QUERY SELECT success, message;
+END;
+$$ LANGUAGE PLPGSQL;
+
+-- the user's calendar feed token, created on first use
+-- only its hash is kept, so feed_token is only returned when a new one is created,
+-- otherwise it is NULL and date_created says when the current one was issued
+-- resetting replaces it, so feeds subscribed with the old one stop working
+DROP FUNCTION IF EXISTS get_calendar_token(TEXT, BOOLEAN);
+CREATE OR REPLACE FUNCTION get_calendar_token (
+	usr TEXT,
+	reset BOOLEAN
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT,
+	feed_token TEXT,
+	date_created TIMESTAMP
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+	feed_token TEXT;
+	date_created TIMESTAMP;
+BEGIN
+	-- default to no token
+	SELECT FALSE, '', NULL, NULL INTO success, message, feed_token, date_created;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	IF (SELECT NOT EXISTS(SELECT 1 FROM user_roles WHERE user_roles.id=usr_id)) THEN
+		SELECT 'Only staff have a calendar' INTO message;
+
+	ELSE
+		IF (reset) THEN
+			DELETE FROM calendar_tokens WHERE userId = usr_id;
+		END IF;
+
+		-- the token is all a calendar app needs to read unpublished headlines
+		SELECT encode(gen_random_bytes(24), 'hex') INTO feed_token;
+		INSERT INTO calendar_tokens(userId, tokenHash, dateCreated)
+			VALUES (usr_id, encode(digest(feed_token, 'sha256'), 'hex'), timezone('utc', now()))
+			ON CONFLICT (userId) DO NOTHING;
+
+		-- the user already had one, which can't be shown again
+		IF (NOT FOUND) THEN
+			SELECT NULL INTO feed_token;
+		END IF;
+
+		SELECT calendar_tokens.dateCreated FROM calendar_tokens WHERE userId = usr_id INTO date_created;
+		SELECT TRUE, CASE WHEN reset THEN 'Calendar token reset' ELSE 'Calendar token' END INTO success, message;
+
+		IF (reset) THEN
+			-- log the result
+			INSERT INTO logs(subject, userId, dateCreated, entry)
+				VALUES ('calendar_token', usr_id, timezone('utc', now()), 'Reset calendar token');
+		END IF;
+	END IF;
+
+	RETURN QUERY SELECT success, message, feed_token, date_created;
+END;
+$$ LANGUAGE PLPGSQL;
+
+-- the active user a calendar feed token belongs to
+CREATE OR REPLACE FUNCTION calendar_user (
+	feed_token TEXT
+)
+RETURNS TEXT
+AS
+$$
+
+	SELECT users.username
+	FROM calendar_tokens
+	JOIN users ON users.id = calendar_tokens.userId
+	WHERE calendar_tokens.tokenHash = encode(digest(feed_token, 'sha256'), 'hex')
+	AND users.active;
+
+$$ LANGUAGE SQL;
@@ -0,0 +1,100 @@
+-- returns the token to email, success is FALSE for unknown or inactive users
+-- but the caller must answer the same either way so accounts can't be discovered
+CREATE OR REPLACE FUNCTION request_password_reset (
+	usr TEXT,
+	request JSONB DEFAULT NULL
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT,
+	reset_token TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+	reset_token TEXT;
+BEGIN
+	-- default to no email
+	SELECT FALSE, '', NULL INTO success, message, reset_token;
+
+	SELECT users.id FROM users WHERE username=usr AND active INTO usr_id;
+
+	IF (usr_id IS NULL) THEN
+		SELECT 'User does not exist' INTO message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry, detail)
+			VALUES ('password_reset', null, timezone('utc', now()), 'Password reset requested for ' || usr || ', who does not exist or is inactive', request);
+
+	ELSE
+		-- anyone with the token can take over the account, so it comes from pgcrypto rather than random()
+		SELECT encode(gen_random_bytes(24), 'hex') INTO reset_token;
+
+		-- only the latest link works
+		DELETE FROM password_resets WHERE userId = usr_id AND dateUsed IS NULL;
+
+		INSERT INTO password_resets(userId, tokenHash, dateCreated, dateExpires)
+			VALUES (usr_id, encode(digest(reset_token, 'sha256'), 'hex'), timezone('utc', now()), timezone('utc', now()) + interval '1 hour');
+
+		SELECT TRUE, 'Password reset requested' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry, detail)
+			VALUES ('password_reset', usr_id, timezone('utc', now()), 'Requested password reset', request);
+	END IF;
+
+	RETURN QUERY SELECT success, message, reset_token;
+END;
+$$ LANGUAGE PLPGSQL;
+
+-- sets the new password and ends every session of the account
+CREATE OR REPLACE FUNCTION reset_password (
+	reset_token TEXT,
+	pass TEXT,
+	confirm TEXT,
+	request JSONB DEFAULT NULL
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT
+)
+AS
+$$
+DECLARE
+	success BOOLEAN;
+	message TEXT;
+	reset password_resets%ROWTYPE;
+BEGIN
+	-- default to not changed
+	SELECT FALSE, '' INTO success, message;
+
+	-- lock the reset so the same link can't be used twice at once
+	SELECT * FROM password_resets WHERE tokenHash = encode(digest(reset_token, 'sha256'), 'hex') FOR UPDATE INTO reset;
+
+	IF (reset.id IS NULL OR reset.dateUsed IS NOT NULL) THEN
+		SELECT 'Reset link does not exist' INTO message;
+
+	ELSIF (reset.dateExpires < timezone('utc', now())) THEN
+		SELECT 'Reset link has expired' INTO message;
+
+	ELSIF (pass <> confirm) THEN
+		SELECT 'Password and confirmed password do not match' INTO message;
+
+	ELSE
+		UPDATE users SET password = crypt(pass, gen_salt('bf', 10)) WHERE id = reset.userId;
+		DELETE FROM sessions WHERE userId = reset.userId;
+		UPDATE password_resets SET dateUsed = timezone('utc', now()) WHERE id = reset.id;
+
+		SELECT TRUE, 'Password changed' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry, detail)
+			VALUES ('password_reset', reset.userId, timezone('utc', now()), 'Reset password', request);
+	END IF;
+
+	RETURN QUERY SELECT success, message;
+END;
+$$ LANGUAGE PLPGSQL;
@@ -0,0 +1,54 @@
+-- returns a new token to email, replacing any earlier one, if usr registered but never confirmed
+-- the caller answers the same either way so accounts can't be discovered
+CREATE OR REPLACE FUNCTION resend_confirmation (
+	usr TEXT,
+	request JSONB DEFAULT NULL
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT,
+	invitation_token TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+	invitation_token TEXT;
+BEGIN
+	-- default to no email
+	SELECT FALSE, '', NULL INTO success, message, invitation_token;
+
+	-- deactivated accounts have no invitation left, so they can't reactivate themselves this way
+	SELECT users.id FROM users
+		WHERE username = usr
+		AND NOT active
+		AND EXISTS(SELECT 1 FROM invitations WHERE invitations.id = users.id)
+		INTO usr_id;
+
+	IF (usr_id IS NULL) THEN
+		SELECT 'Invitation does not exist' INTO message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry, detail)
+			VALUES ('confirmation', null, timezone('utc', now()), 'Confirmation resend requested for ' || usr || ', who has nothing to confirm', request);
+
+	ELSE
+		SELECT encode(gen_random_bytes(24), 'hex') INTO invitation_token;
+
+		-- only the latest email works
+		DELETE FROM invitations WHERE id = usr_id;
+		INSERT INTO invitations(id, invitation, dateCreated)
+			VALUES (usr_id, encode(digest(invitation_token, 'sha256'), 'hex'), timezone('utc', now()));
+
+		SELECT TRUE, 'Invitation resent' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry, detail)
+			VALUES ('confirmation', usr_id, timezone('utc', now()), 'Resent confirmation email', request);
+	END IF;
+
+	RETURN QUERY SELECT success, message, invitation_token;
+END;
+$$ LANGUAGE PLPGSQL;
@@ -0,0 +1,225 @@
+-- free attempts before backing off, and failures before locking out, for each scope
+CREATE OR REPLACE FUNCTION throttle_limits (
+	throttle_scope TEXT
+)
+RETURNS TABLE (
+	free_attempts INTEGER,
+	lockout_after INTEGER
+)
+AS
+$$
+
+	SELECT CASE throttle_scope
+			WHEN 'login_user' THEN 3
+			WHEN 'two_factor_user' THEN 3
+			WHEN 'login_ip' THEN 20
+			ELSE 5
+		END,
+		CASE throttle_scope
+			WHEN 'login_user' THEN 10
+			WHEN 'two_factor_user' THEN 10
+			WHEN 'login_ip' THEN 100
+			ELSE 20
+		END;
+
+$$ LANGUAGE SQL;
+
+-- seconds until every one of the (scope, key) pairs may try again, 0 if none are blocked
+CREATE OR REPLACE FUNCTION throttle_wait (
+	scopes TEXT[],
+	keys TEXT[]
+)
+RETURNS INTEGER
+AS
+$$
+
+	SELECT coalesce(ceil(max(extract(EPOCH FROM throttles.blockedUntil - timezone('utc', now()))))::INTEGER, 0)
+	FROM throttles
+	JOIN unnest(scopes, keys) AS attempt(scope, key) USING (scope, key)
+	WHERE throttles.blockedUntil > timezone('utc', now());
+
+$$ LANGUAGE SQL;
+
+-- counts a failure for throttle_attempt and blocks further tries
+-- after free_attempts, each further failure doubles the wait from one second,
+-- and from lockout_after the key is locked out for 15 minutes, doubling up to a day
+-- failures are forgotten after an hour without one
+CREATE OR REPLACE FUNCTION throttle_failure (
+	failure_scope TEXT,
+	failure_key TEXT,
+	request JSONB DEFAULT NULL
+)
+RETURNS INTEGER
+AS
+$$
+DECLARE
+	free_attempts INTEGER;
+	lockout_after INTEGER;
+	failure_count INTEGER;
+	blocked TIMESTAMP;
+BEGIN
+	SELECT * FROM throttle_limits(failure_scope) INTO free_attempts, lockout_after;
+
+	INSERT INTO throttles(scope, key, failures, lastFailure)
+		VALUES (failure_scope, failure_key, 1, timezone('utc', now()))
+		ON CONFLICT (scope, key) DO UPDATE SET
+			failures = CASE WHEN throttles.lastFailure < timezone('utc', now()) - interval '1 hour' THEN 1 ELSE throttles.failures + 1 END,
+			lastFailure = timezone('utc', now())
+		RETURNING failures INTO failure_count;
+
+	IF (failure_count >= lockout_after) THEN
+		SELECT timezone('utc', now()) + least(interval '15 minutes' * power(2, failure_count - lockout_after), interval '1 day') INTO blocked;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry, detail)
+			VALUES ('lockout', null, timezone('utc', now()),
+				'Locked out ' || failure_scope || ' ' || failure_key || ' until ' || cast(blocked as TEXT) || ' after ' || cast(failure_count as TEXT) || ' failures',
+				request);
+
+	ELSIF (failure_count > free_attempts) THEN
+		SELECT timezone('utc', now()) + interval '1 second' * power(2, failure_count - free_attempts - 1) INTO blocked;
+	END IF;
+
+	UPDATE throttles SET blockedUntil = blocked WHERE scope = failure_scope AND key = failure_key;
+
+	RETURN failure_count;
+END;
+$$ LANGUAGE PLPGSQL;
+
+-- called before the attempt is checked, returns the seconds to wait if any of the pairs is blocked,
+-- otherwise counts the attempt as a failure for all of them and returns 0
+-- the rows are locked first, so of several attempts sent at once only as many get through as are allowed
+-- keys are chosen by whoever sends the request, so rows that no longer block anything are cleared out here,
+-- rows another attempt has locked are skipped rather than waited for
+CREATE OR REPLACE FUNCTION throttle_attempt (
+	scopes TEXT[],
+	keys TEXT[],
+	request JSONB DEFAULT NULL
+)
+RETURNS INTEGER
+AS
+$$
+DECLARE
+	wait INTEGER;
+BEGIN
+	DELETE FROM throttles WHERE ctid IN (
+		SELECT ctid FROM throttles
+		WHERE lastFailure < timezone('utc', now()) - interval '1 hour'
+			AND (blockedUntil IS NULL OR blockedUntil < timezone('utc', now()))
+		FOR UPDATE SKIP LOCKED);
+
+	INSERT INTO throttles(scope, key, failures, lastFailure)
+		SELECT attempt.scope, attempt.key, 0, timezone('utc', now())
+		FROM unnest(scopes, keys) AS attempt(scope, key)
+		ORDER BY 1, 2
+		ON CONFLICT (scope, key) DO NOTHING;
+
+	PERFORM 1 FROM throttles
+		JOIN unnest(scopes, keys) AS attempt(scope, key) USING (scope, key)
+		ORDER BY scope, key
+		FOR UPDATE OF throttles;
+
+	SELECT throttle_wait(scopes, keys) INTO wait;
+
+	IF (wait = 0) THEN
+		PERFORM throttle_failure(attempt.scope, attempt.key, request)
+			FROM unnest(scopes, keys) AS attempt(scope, key);
+	END IF;
+
+	RETURN wait;
+END;
+$$ LANGUAGE PLPGSQL;
+
+-- takes back an attempt counted by throttle_attempt that didn't fail,
+-- the backoff it started is lifted if that leaves no more than the free attempts
+CREATE OR REPLACE FUNCTION throttle_refund (
+	refund_scope TEXT,
+	refund_key TEXT
+)
+RETURNS VOID
+AS
+$$
+
+	UPDATE throttles
+	SET failures = greatest(throttles.failures - 1, 0),
+		blockedUntil = CASE WHEN throttles.failures - 1 <= limits.free_attempts THEN NULL ELSE throttles.blockedUntil END
+	FROM throttle_limits(refund_scope) AS limits
+	WHERE throttles.scope = refund_scope AND throttles.key = refund_key;
+
+$$ LANGUAGE SQL;
+
+-- a successful login forgets the username's failures, the ip only gets its attempt refunded
+CREATE OR REPLACE FUNCTION throttle_success (
+	success_scope TEXT,
+	success_key TEXT
+)
+RETURNS VOID
+AS
+$$
+
+	DELETE FROM throttles WHERE scope = success_scope AND key = success_key;
+
+$$ LANGUAGE SQL;
+
+-- current backoffs and lockouts, nothing unless usr is an admin
+CREATE OR REPLACE FUNCTION get_throttles (
+	usr TEXT
+)
+RETURNS TABLE (
+	scope TEXT,
+	key TEXT,
+	failures INTEGER,
+	lastFailure TIMESTAMP,
+	blockedUntil TIMESTAMP
+)
+AS
+$$
+
+	SELECT throttles.scope, throttles.key, throttles.failures, throttles.lastFailure, throttles.blockedUntil
+	FROM throttles
+	WHERE authorize(usr, 1)
+	AND throttles.blockedUntil > timezone('utc', now())
+	ORDER BY throttles.blockedUntil DESC;
+
+$$ LANGUAGE SQL;
+
+CREATE OR REPLACE FUNCTION clear_throttle (
+	usr TEXT,
+	throttle_scope TEXT,
+	throttle_key TEXT
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+BEGIN
+	-- default to not changed
+	SELECT FALSE, '' INTO success, message;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	IF (NOT authorize(usr, 1)) THEN
+		SELECT 'Only admins can clear lockouts' INTO message;
+
+	ELSIF (SELECT NOT EXISTS(SELECT 1 FROM throttles WHERE scope = throttle_scope AND key = throttle_key)) THEN
+		SELECT 'Lockout does not exist' INTO message;
+
+	ELSE
+		DELETE FROM throttles WHERE scope = throttle_scope AND key = throttle_key;
+
+		SELECT TRUE, 'Lockout cleared' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('lockout', usr_id, timezone('utc', now()), 'Cleared ' || throttle_scope || ' ' || throttle_key);
+	END IF;
+
+	RETURN QUERY SELECT success, message;
+END;
+$$ LANGUAGE PLPGSQL;
@@ -0,0 +1,187 @@
+-- called after authenticate succeeds, returns the token for the auth cookie
+-- anyone with the token is logged in, so it comes from pgcrypto rather than random()
+-- if the user has two-factor authentication the session is pending until complete_login
+DROP FUNCTION IF EXISTS create_session(TEXT, TEXT, TEXT);
+CREATE OR REPLACE FUNCTION create_session (
+	usr TEXT,
+	client_ip TEXT,
+	client_agent TEXT
+)
+RETURNS TABLE (
+	session_token TEXT,
+	pending BOOLEAN
+)
+AS
+$$
+DECLARE
+	session_token TEXT;
+	pending BOOLEAN;
+BEGIN
+	SELECT encode(gen_random_bytes(24), 'hex') INTO session_token;
+
+	SELECT EXISTS(SELECT 1 FROM two_factor JOIN users ON users.id = two_factor.userId
+		WHERE users.username = usr AND two_factor.dateConfirmed IS NOT NULL) INTO pending;
+
+	-- sessions last as long as the cookie, expired ones are cleared out on every login
+	DELETE FROM sessions WHERE dateExpires < timezone('utc', now()) OR pendingUntil < timezone('utc', now());
+
+	INSERT INTO sessions(userId, tokenHash, dateCreated, dateExpires, lastSeen, ip, userAgent, pendingUntil)
+		SELECT users.id, encode(digest(session_token, 'sha256'), 'hex'), timezone('utc', now()), timezone('utc', now()) + interval '7 days', timezone('utc', now()), client_ip, client_agent,
+			CASE WHEN pending THEN timezone('utc', now()) + interval '5 minutes' END
+		FROM users
+		WHERE users.username = usr;
+
+	RETURN QUERY SELECT session_token, pending;
+END;
+$$ LANGUAGE PLPGSQL;
+
+-- who a cookie belongs to and their current roles, no row if the session was revoked or expired
+-- or the account has been deactivated since, so none of those wait for the cookie to expire
+-- needs_two_factor is set if one of the roles requires two-factor authentication the user hasn't set up
+DROP FUNCTION IF EXISTS session_credentials(TEXT, TEXT, TEXT);
+CREATE OR REPLACE FUNCTION session_credentials (
+	session_token TEXT,
+	client_ip TEXT,
+	client_agent TEXT
+)
+RETURNS TABLE (
+	username TEXT,
+	roles INTEGER[],
+	needs_two_factor BOOLEAN
+)
+AS
+$$
+
+	UPDATE sessions SET lastSeen = timezone('utc', now()), ip = client_ip, userAgent = client_agent
+	FROM users
+	WHERE sessions.tokenHash = encode(digest(session_token, 'sha256'), 'hex')
+	AND sessions.dateExpires > timezone('utc', now())
+	AND sessions.pendingUntil IS NULL
+	AND users.id = sessions.userId
+	AND users.active
+	RETURNING users.username, coalesce(check_roles(users.username), ARRAY[]::INTEGER[]),
+		EXISTS(SELECT 1 FROM user_roles JOIN roles ON roles.id = user_roles.role
+			WHERE user_roles.id = users.id AND roles.requireTwoFactor)
+		AND NOT EXISTS(SELECT 1 FROM two_factor
+			WHERE two_factor.userId = users.id AND two_factor.dateConfirmed IS NOT NULL);
+
+$$ LANGUAGE SQL;
+
+-- the user's own sessions, most recently used first, current is the one making the request
+CREATE OR REPLACE FUNCTION get_sessions (
+	usr TEXT,
+	session_token TEXT
+)
+RETURNS TABLE (
+	id INTEGER,
+	dateCreated TIMESTAMP,
+	lastSeen TIMESTAMP,
+	ip TEXT,
+	userAgent TEXT,
+	current BOOLEAN
+)
+AS
+$$
+
+	SELECT sessions.id, sessions.dateCreated, sessions.lastSeen, sessions.ip, sessions.userAgent,
+		sessions.tokenHash = encode(digest(session_token, 'sha256'), 'hex')
+	FROM sessions
+	JOIN users ON users.id = sessions.userId
+	WHERE users.username = usr
+	AND sessions.dateExpires > timezone('utc', now())
+	AND sessions.pendingUntil IS NULL
+	ORDER BY sessions.lastSeen DESC;
+
+$$ LANGUAGE SQL;
+
+-- users can only revoke their own sessions
+CREATE OR REPLACE FUNCTION revoke_session (
+	usr TEXT,
+	session_id INTEGER
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+BEGIN
+	-- default to not changed
+	SELECT FALSE, '' INTO success, message;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	DELETE FROM sessions WHERE sessions.id = session_id AND sessions.userId = usr_id;
+
+	IF (NOT FOUND) THEN
+		SELECT 'Session does not exist' INTO message;
+
+	ELSE
+		SELECT TRUE, 'Session revoked' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('session', usr_id, timezone('utc', now()), 'Revoked session ' || session_id);
+	END IF;
+
+	RETURN QUERY SELECT success, message;
+END;
+$$ LANGUAGE PLPGSQL;
+
+-- logs the user out everywhere, except the session with keep_token if given
+CREATE OR REPLACE FUNCTION revoke_sessions (
+	usr TEXT,
+	keep_token TEXT DEFAULT NULL
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	revoked INTEGER;
+BEGIN
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	DELETE FROM sessions
+		WHERE sessions.userId = usr_id
+		AND sessions.tokenHash IS DISTINCT FROM encode(digest(keep_token, 'sha256'), 'hex');
+	GET DIAGNOSTICS revoked = ROW_COUNT;
+
+	-- log the result
+	INSERT INTO logs(subject, userId, dateCreated, entry)
+		VALUES ('session', usr_id, timezone('utc', now()), CASE WHEN keep_token IS NULL
+			THEN 'Logged out everywhere'
+			ELSE 'Logged out other sessions' END);
+
+	RETURN QUERY SELECT TRUE, 'Revoked ' || revoked || ' sessions';
+END;
+$$ LANGUAGE PLPGSQL;
+
+-- logout, unknown tokens are fine since the cookie is forgotten either way
+CREATE OR REPLACE FUNCTION end_session (
+	session_token TEXT
+)
+RETURNS VOID
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+BEGIN
+	DELETE FROM sessions
+		WHERE tokenHash = encode(digest(session_token, 'sha256'), 'hex')
+		RETURNING userId INTO usr_id;
+
+	IF (usr_id IS NOT NULL) THEN
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('session', usr_id, timezone('utc', now()), 'Logged out');
+	END IF;
+END;
+$$ LANGUAGE PLPGSQL;
@@ -0,0 +1,472 @@
+-- RFC 4648 base32 without padding, authenticator apps expect the secret in this form
+CREATE OR REPLACE FUNCTION base32 (
+	data BYTEA
+)
+RETURNS TEXT
+AS
+$$
+DECLARE
+	alphabet TEXT := 'ABCDEFGHIJKLMNOPQRSTUVWXYZ234567';
+	bits TEXT := '';
+	encoded TEXT := '';
+BEGIN
+	FOR i IN 0 .. length(data) - 1 LOOP
+		bits := bits || get_byte(data, i)::BIT(8)::TEXT;
+	END LOOP;
+
+	-- every character is 5 bits, the last one is padded with zeros
+	bits := rpad(bits, (length(bits) + 4) / 5 * 5, '0');
+
+	FOR i IN 0 .. length(bits) / 5 - 1 LOOP
+		encoded := encoded || substr(alphabet, substr(bits, i * 5 + 1, 5)::BIT(5)::INTEGER + 1, 1);
+	END LOOP;
+
+	RETURN encoded;
+END;
+$$ LANGUAGE PLPGSQL IMMUTABLE;
+
+-- RFC 4226 HOTP with 6 digits, TOTP (RFC 6238) uses the number of 30 second steps since 1970 as the counter
+CREATE OR REPLACE FUNCTION hotp (
+	secret BYTEA,
+	counter BIGINT
+)
+RETURNS TEXT
+AS
+$$
+DECLARE
+	mac BYTEA;
+	truncate_at INTEGER;
+	truncated BIGINT;
+BEGIN
+	SELECT hmac(int8send(counter), secret, 'sha1') INTO mac;
+	SELECT get_byte(mac, 19) & 15 INTO truncate_at;
+
+	-- bitwise operators all have the same precedence, hence the brackets
+	SELECT ((get_byte(mac, truncate_at) & 127)::BIGINT << 24)
+		| (get_byte(mac, truncate_at + 1) << 16)
+		| (get_byte(mac, truncate_at + 2) << 8)
+		| get_byte(mac, truncate_at + 3)
+		INTO truncated;
+
+	RETURN lpad((truncated % 1000000)::TEXT, 6, '0');
+END;
+$$ LANGUAGE PLPGSQL IMMUTABLE;
+
+-- the time step a code belongs to, codes from one step either side of now are accepted for clock drift
+-- NULL if it is wrong or not newer than after_step, so a code can't be used twice
+CREATE OR REPLACE FUNCTION totp_step (
+	secret BYTEA,
+	code TEXT,
+	after_step BIGINT
+)
+RETURNS BIGINT
+AS
+$$
+
+	SELECT max(step)
+	FROM generate_series(floor(extract(EPOCH FROM now()) / 30)::BIGINT - 1, floor(extract(EPOCH FROM now()) / 30)::BIGINT + 1) AS step
+	WHERE step > after_step
+	AND hotp(secret, step) = regexp_replace(code, '\s', '', 'g');
+
+$$ LANGUAGE SQL;
+
+-- true if one of the user's roles requires two-factor authentication
+CREATE OR REPLACE FUNCTION two_factor_required (
+	usr_id INTEGER
+)
+RETURNS BOOLEAN
+AS
+$$
+
+	SELECT EXISTS(SELECT 1 FROM user_roles JOIN roles ON roles.id = user_roles.role
+		WHERE user_roles.id = usr_id AND roles.requireTwoFactor);
+
+$$ LANGUAGE SQL;
+
+-- replaces the user's recovery codes with 10 new ones, they are only ever returned here
+CREATE OR REPLACE FUNCTION generate_recovery_codes (
+	usr_id INTEGER
+)
+RETURNS TEXT[]
+AS
+$$
+DECLARE
+	codes TEXT[];
+BEGIN
+	SELECT array_agg(substr(code, 1, 5) || '-' || substr(code, 6))
+		FROM (SELECT encode(gen_random_bytes(5), 'hex') AS code FROM generate_series(1, 10)) AS generated
+		INTO codes;
+
+	DELETE FROM recovery_codes WHERE userId = usr_id;
+
+	INSERT INTO recovery_codes(userId, codeHash)
+		SELECT usr_id, encode(digest(replace(code, '-', ''), 'sha256'), 'hex')
+		FROM unnest(codes) AS code;
+
+	RETURN codes;
+END;
+$$ LANGUAGE PLPGSQL;
+
+-- accepts a code from the authenticator app or an unused recovery code, and uses it up
+CREATE OR REPLACE FUNCTION use_two_factor_code (
+	usr_id INTEGER,
+	code TEXT
+)
+RETURNS BOOLEAN
+AS
+$$
+DECLARE
+	factor two_factor%ROWTYPE;
+	accepted_step BIGINT;
+BEGIN
+	-- lock the secret so the same code can't be accepted twice at once
+	SELECT * FROM two_factor WHERE userId = usr_id AND dateConfirmed IS NOT NULL FOR UPDATE INTO factor;
+
+	IF (factor.userId IS NULL) THEN
+		RETURN FALSE;
+	END IF;
+
+	SELECT totp_step(factor.secret, code, factor.lastStep) INTO accepted_step;
+
+	IF (accepted_step IS NOT NULL) THEN
+		UPDATE two_factor SET lastStep = accepted_step WHERE userId = usr_id;
+		RETURN TRUE;
+	END IF;
+
+	-- recovery codes are shown with a dash, and may be typed in either case
+	UPDATE recovery_codes SET dateUsed = timezone('utc', now())
+		WHERE userId = usr_id
+		AND dateUsed IS NULL
+		AND codeHash = encode(digest(lower(regexp_replace(code, '[^0-9a-zA-Z]', '', 'g')), 'sha256'), 'hex');
+
+	IF (FOUND) THEN
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('two_factor', usr_id, timezone('utc', now()), 'Used a recovery code');
+		RETURN TRUE;
+	END IF;
+
+	RETURN FALSE;
+END;
+$$ LANGUAGE PLPGSQL;
+
+CREATE OR REPLACE FUNCTION get_two_factor (
+	usr TEXT
+)
+RETURNS TABLE (
+	enabled BOOLEAN,
+	required BOOLEAN,
+	recoveryCodes INTEGER
+)
+AS
+$$
+
+	SELECT coalesce(two_factor.dateConfirmed IS NOT NULL, FALSE),
+		two_factor_required(users.id),
+		(SELECT count(*)::INTEGER FROM recovery_codes WHERE recovery_codes.userId = users.id AND recovery_codes.dateUsed IS NULL)
+	FROM users
+	LEFT JOIN two_factor ON two_factor.userId = users.id
+	WHERE users.username = usr;
+
+$$ LANGUAGE SQL;
+
+-- a new secret for the authenticator app, it replaces any earlier setup that was never confirmed
+CREATE OR REPLACE FUNCTION start_two_factor (
+	usr TEXT
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT,
+	secret TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+	secret TEXT;
+	new_secret BYTEA;
+BEGIN
+	-- default to not changed
+	SELECT FALSE, '', NULL INTO success, message, secret;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	IF (SELECT EXISTS(SELECT 1 FROM two_factor WHERE userId = usr_id AND dateConfirmed IS NOT NULL)) THEN
+		SELECT 'Two-factor authentication already exists' INTO message;
+
+	ELSE
+		-- 160 bits, the size RFC 4226 recommends for HMAC-SHA1
+		SELECT gen_random_bytes(20) INTO new_secret;
+
+		INSERT INTO two_factor(userId, secret, dateCreated)
+			VALUES (usr_id, new_secret, timezone('utc', now()))
+			ON CONFLICT (userId) DO UPDATE SET secret = new_secret, dateCreated = timezone('utc', now()), lastStep = 0;
+
+		SELECT TRUE, 'Two-factor setup started', base32(new_secret) INTO success, message, secret;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('two_factor', usr_id, timezone('utc', now()), 'Started two-factor setup');
+	END IF;
+
+	RETURN QUERY SELECT success, message, secret;
+END;
+$$ LANGUAGE PLPGSQL;
+
+-- turns two-factor authentication on once the app shows the right code, and returns the recovery codes
+-- every other session of the user was logged in without it, so they are ended
+CREATE OR REPLACE FUNCTION confirm_two_factor (
+	usr TEXT,
+	code TEXT,
+	session_token TEXT
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT,
+	codes TEXT[]
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+	codes TEXT[];
+	factor two_factor%ROWTYPE;
+	accepted_step BIGINT;
+BEGIN
+	-- default to not changed
+	SELECT FALSE, '', NULL INTO success, message, codes;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	SELECT * FROM two_factor WHERE userId = usr_id FOR UPDATE INTO factor;
+
+	IF (factor.userId IS NULL) THEN
+		SELECT 'Two-factor setup does not exist' INTO message;
+
+	ELSIF (factor.dateConfirmed IS NOT NULL) THEN
+		SELECT 'Two-factor authentication already exists' INTO message;
+
+	ELSIF (factor.dateCreated < timezone('utc', now()) - interval '1 hour') THEN
+		SELECT 'Two-factor setup has expired' INTO message;
+
+	ELSE
+		SELECT totp_step(factor.secret, code, 0) INTO accepted_step;
+
+		IF (accepted_step IS NULL) THEN
+			SELECT 'Wrong code' INTO message;
+
+		ELSE
+			UPDATE two_factor SET dateConfirmed = timezone('utc', now()), lastStep = accepted_step WHERE userId = usr_id;
+			DELETE FROM sessions
+				WHERE userId = usr_id
+				AND tokenHash IS DISTINCT FROM encode(digest(session_token, 'sha256'), 'hex');
+
+			SELECT TRUE, 'Two-factor authentication enabled', generate_recovery_codes(usr_id) INTO success, message, codes;
+
+			-- log the result
+			INSERT INTO logs(subject, userId, dateCreated, entry)
+				VALUES ('two_factor', usr_id, timezone('utc', now()), 'Enabled two-factor authentication');
+		END IF;
+	END IF;
+
+	RETURN QUERY SELECT success, message, codes;
+END;
+$$ LANGUAGE PLPGSQL;
+
+-- new recovery codes replace all the old ones, needs a current code
+CREATE OR REPLACE FUNCTION reset_recovery_codes (
+	usr TEXT,
+	code TEXT
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT,
+	codes TEXT[]
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+	codes TEXT[];
+BEGIN
+	-- default to not changed
+	SELECT FALSE, '', NULL INTO success, message, codes;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	IF (SELECT NOT EXISTS(SELECT 1 FROM two_factor WHERE userId = usr_id AND dateConfirmed IS NOT NULL)) THEN
+		SELECT 'Two-factor authentication does not exist' INTO message;
+
+	ELSIF (NOT use_two_factor_code(usr_id, code)) THEN
+		SELECT 'Wrong code' INTO message;
+
+	ELSE
+		SELECT TRUE, 'Recovery codes replaced', generate_recovery_codes(usr_id) INTO success, message, codes;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('two_factor', usr_id, timezone('utc', now()), 'Replaced recovery codes');
+	END IF;
+
+	RETURN QUERY SELECT success, message, codes;
+END;
+$$ LANGUAGE PLPGSQL;
+
+-- needs a current code, and isn't allowed while one of the user's roles requires two-factor authentication
+CREATE OR REPLACE FUNCTION disable_two_factor (
+	usr TEXT,
+	code TEXT
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+BEGIN
+	-- default to not changed
+	SELECT FALSE, '' INTO success, message;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	IF (SELECT NOT EXISTS(SELECT 1 FROM two_factor WHERE userId = usr_id AND dateConfirmed IS NOT NULL)) THEN
+		SELECT 'Two-factor authentication does not exist' INTO message;
+
+	ELSIF (two_factor_required(usr_id)) THEN
+		SELECT 'Only users without a role that requires it can turn off two-factor authentication' INTO message;
+
+	ELSIF (NOT use_two_factor_code(usr_id, code)) THEN
+		SELECT 'Wrong code' INTO message;
+
+	ELSE
+		DELETE FROM two_factor WHERE userId = usr_id;
+		DELETE FROM recovery_codes WHERE userId = usr_id;
+
+		SELECT TRUE, 'Two-factor authentication disabled' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('two_factor', usr_id, timezone('utc', now()), 'Disabled two-factor authentication');
+	END IF;
+
+	RETURN QUERY SELECT success, message;
+END;
+$$ LANGUAGE PLPGSQL;
+
+-- the user a login waiting for its second step belongs to, NULL if there is none or it took too long
+CREATE OR REPLACE FUNCTION pending_login (
+	session_token TEXT
+)
+RETURNS TEXT
+AS
+$$
+
+	SELECT users.username
+	FROM sessions
+	JOIN users ON users.id = sessions.userId
+	WHERE sessions.tokenHash = encode(digest(session_token, 'sha256'), 'hex')
+	AND sessions.pendingUntil > timezone('utc', now());
+
+$$ LANGUAGE SQL;
+
+-- second step of logging in, the session from create_session can be used once the code is accepted
+CREATE OR REPLACE FUNCTION complete_login (
+	session_token TEXT,
+	code TEXT,
+	request JSONB DEFAULT NULL
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT
+)
+AS
+$$
+DECLARE
+	success BOOLEAN;
+	message TEXT;
+	pending sessions%ROWTYPE;
+BEGIN
+	-- default to not logged in
+	SELECT FALSE, '' INTO success, message;
+
+	SELECT * FROM sessions
+		WHERE tokenHash = encode(digest(session_token, 'sha256'), 'hex')
+		AND pendingUntil > timezone('utc', now())
+		INTO pending;
+
+	IF (pending.id IS NULL) THEN
+		SELECT 'Login does not exist' INTO message;
+
+	ELSIF (NOT use_two_factor_code(pending.userId, code)) THEN
+		SELECT 'Wrong code' INTO message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry, detail)
+			VALUES ('login', pending.userId, timezone('utc', now()), 'Wrong two-factor code', request);
+
+	ELSE
+		UPDATE sessions SET pendingUntil = NULL WHERE id = pending.id;
+
+		SELECT TRUE, 'Success' INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry, detail)
+			VALUES ('login', pending.userId, timezone('utc', now()), 'Logged in with two-factor code', request);
+	END IF;
+
+	RETURN QUERY SELECT success, message;
+END;
+$$ LANGUAGE PLPGSQL;
+
+-- admins can require two-factor authentication for everyone with a role
+CREATE OR REPLACE FUNCTION set_role_two_factor (
+	usr TEXT,
+	role_id INTEGER,
+	required BOOLEAN
+)
+RETURNS TABLE (
+	success BOOLEAN,
+	message TEXT
+)
+AS
+$$
+DECLARE
+	usr_id INTEGER;
+	success BOOLEAN;
+	message TEXT;
+BEGIN
+	-- default to not changed
+	SELECT FALSE, '' INTO success, message;
+
+	SELECT users.id FROM users WHERE username=usr INTO usr_id;
+
+	IF (NOT authorize(usr, 1)) THEN
+		SELECT 'Only admins can change two-factor requirements' INTO message;
+
+	ELSIF (SELECT NOT EXISTS(SELECT 1 FROM roles WHERE id = role_id)) THEN
+		SELECT 'Role does not exist' INTO message;
+
+	ELSE
+		UPDATE roles SET requireTwoFactor = required WHERE id = role_id;
+
+		SELECT TRUE, CASE WHEN required THEN 'Two-factor authentication required' ELSE 'Two-factor authentication optional' END INTO success, message;
+
+		-- log the result
+		INSERT INTO logs(subject, userId, dateCreated, entry)
+			VALUES ('two_factor', usr_id, timezone('utc', now()),
+				CASE WHEN required THEN 'Required' ELSE 'Stopped requiring' END || ' two-factor authentication for role ' || role_id);
+	END IF;
+
+	RETURN QUERY SELECT success, message;
+END;
+$$ LANGUAGE PLPGSQL;
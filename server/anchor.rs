@@ -0,0 +1,91 @@
+// Anchors tie a comment to a character range of a draft body.
+// Offsets count chars (Unicode scalar values), not bytes.
+
+// how much surrounding text is kept to find the range again after edits
+const CONTEXT: usize = 32;
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Anchor {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+    pub prefix: String,
+    pub suffix: String,
+}
+
+pub fn capture(body: &str, start: usize, end: usize) -> Option<Anchor> {
+    let chars: Vec<char> = body.chars().collect();
+    if start >= end || end > chars.len() {
+        return None;
+    }
+    Some(Anchor {
+        start,
+        end,
+        text: chars[start..end].iter().collect(),
+        prefix: chars[start.saturating_sub(CONTEXT)..start].iter().collect(),
+        suffix: chars[end..(end + CONTEXT).min(chars.len())].iter().collect(),
+    })
+}
+
+// finds the anchored text in an edited body, None if it can no longer be placed
+pub fn reanchor(body: &str, anchor: &Anchor) -> Option<Anchor> {
+    let chars: Vec<char> = body.chars().collect();
+    let text: Vec<char> = anchor.text.chars().collect();
+    let prefix: Vec<char> = anchor.prefix.chars().collect();
+    let suffix: Vec<char> = anchor.suffix.chars().collect();
+
+    // unchanged at the old position
+    if anchor.end <= chars.len() && chars[anchor.start..anchor.end] == text[..] {
+        return capture(body, anchor.start, anchor.end);
+    }
+
+    // the text moved: pick the occurrence whose surroundings match best, then the nearest
+    let best = positions(&chars, &text)
+        .into_iter()
+        .max_by_key(|&i| {
+            let context = common_suffix(&chars[..i], &prefix) + common_prefix(&chars[i + text.len()..], &suffix);
+            let distance = (i as isize - anchor.start as isize).abs();
+            (context, -distance)
+        });
+    if let Some(i) = best {
+        return capture(body, i, i + text.len());
+    }
+
+    // the text itself was edited: take whatever now sits between its old surroundings
+    if prefix.is_empty() || suffix.is_empty() {
+        return None;
+    }
+    let max_len = (text.len() * 2).max(text.len() + CONTEXT);
+    positions(&chars, &prefix)
+        .into_iter()
+        .map(|p| p + prefix.len())
+        .filter_map(|start| {
+            positions(&chars[start..], &suffix)
+                .into_iter()
+                .next()
+                .filter(|&len| len > 0 && len <= max_len)
+                .map(|len| (start, start + len))
+        })
+        .min_by_key(|&(start, _)| (start as isize - anchor.start as isize).abs())
+        .and_then(|(start, end)| capture(body, start, end))
+}
+
+fn positions(haystack: &[char], needle: &[char]) -> Vec<usize> {
+    if needle.is_empty() || needle.len() > haystack.len() {
+        return Vec::new();
+    }
+    haystack
+        .windows(needle.len())
+        .enumerate()
+        .filter(|(_, window)| *window == needle)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+fn common_prefix(a: &[char], b: &[char]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+fn common_suffix(a: &[char], b: &[char]) -> usize {
+    a.iter().rev().zip(b.iter().rev()).take_while(|(x, y)| x == y).count()
+}
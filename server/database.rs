@@ -4,11 +4,16 @@ use std::fs;
 use tokio_postgres::{NoTls};
 use r2d2_postgres::PostgresConnectionManager;
 use actix_web::{web};
+use actix_web::error::BlockingError;
 use serde::{Serialize, Deserialize};
 use glob::glob;
 use uuid::Uuid;
 use log::info;
 
+use crate::anchor;
+use crate::markdown;
+use crate::text;
+
 
 pub type DB = r2d2::Pool<PostgresConnectionManager<NoTls>>;
 pub type DBPool = r2d2::PooledConnection<PostgresConnectionManager<NoTls>>;
@@ -21,6 +26,10 @@ pub enum DBError {
     PoolError(r2d2::Error),
     TokioPostgresError(tokio_postgres::error::Error),
     AuthenticationError(String),
+    NotFoundError(String),
+    ValidationError(String),
+    ConflictError(String),
+    GoneError(String),
     OtherError(String),
 }
 
@@ -41,11 +50,124 @@ pub struct Register {
 pub struct Credentials {
     pub username: String,
     pub roles: Vec<i32>,
+    // one of the roles requires two-factor authentication, and the user can only set it up until they have
+    #[serde(default)]
+    pub needs_two_factor: bool,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct ResendConfirmation {
+    pub username: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct ForgotPassword {
+    pub username: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct PasswordReset {
+    pub password: String,
+    pub confirm: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct NewStaffInvitation {
+    pub email: String,
+    pub roles: Vec<i32>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct AcceptInvitation {
+    pub password: String,
+    pub confirm: String,
+}
+
+#[derive(Serialize, PartialEq, Clone)]
+pub struct StaffInvitation {
+    pub id: i32,
+    pub email: String,
+    pub roles: Vec<i32>,
+    pub invited_by: String,
+    pub date_created: std::time::SystemTime,
+    pub date_expires: std::time::SystemTime,
+}
+
+#[derive(Serialize, PartialEq, Clone)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    pub required: bool,
+    pub recovery_codes: i32,
+}
+
+// shown once, each can be used instead of a code from the authenticator app
+#[derive(Serialize, PartialEq, Clone)]
+pub struct RecoveryCodes {
+    pub codes: Vec<String>,
+}
+
+// a code from the authenticator app, or a recovery code where those are accepted
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct TwoFactorCode {
+    pub code: String,
+}
+
+// a logged in browser, ip and user agent are from its latest request
+#[derive(Serialize, PartialEq, Clone)]
+pub struct Session {
+    pub id: i32,
+    pub date_created: std::time::SystemTime,
+    pub last_seen: std::time::SystemTime,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub current: bool,
+}
+
+// scope is login_user, two_factor_user, resend_user, reset_user or the _ip scopes for login, confirm,
+// register, resend and reset, key the username or ip
+#[derive(Serialize, PartialEq, Clone)]
+pub struct Throttle {
+    pub scope: String,
+    pub key: String,
+    pub failures: i32,
+    pub last_failure: std::time::SystemTime,
+    pub blocked_until: Option<std::time::SystemTime>,
+}
+
+#[derive(Serialize, PartialEq, Clone)]
+pub struct LogEntry {
+    pub id: i32,
+    pub subject: String,
+    pub user_id: Option<i32>,
+    pub username: Option<String>,
+    pub date_created: std::time::SystemTime,
+    pub entry: Option<String>,
+    pub detail: Option<serde_json::Value>,
+}
+
+#[derive(Clone)]
+pub struct LogFilter {
+    pub subject: Option<String>,
+    pub user: Option<String>,
+    pub since: Option<std::time::SystemTime>,
+    pub until: Option<std::time::SystemTime>,
+    pub search: Option<String>,
+    pub after: Option<i32>,
+}
+
+#[derive(Serialize, PartialEq, Clone)]
+pub struct UserAccount {
+    pub id: i32,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub active: bool,
+    pub created: std::time::SystemTime,
+    pub roles: Vec<i32>,
 }
 
 //  roles spec
 #[derive(Copy, Clone)]
-enum Role {
+pub enum Role {
     Admin = 1,
     Author = 2,
     Reviewer = 3,
@@ -59,6 +181,10 @@ impl fmt::Display for DBError {
             DBError::PoolError(ref e) => ::std::fmt::Display::fmt(e, f),
             DBError::TokioPostgresError(ref e) => ::std::fmt::Display::fmt(e, f),
             DBError::AuthenticationError(ref e) => ::std::fmt::Display::fmt(e, f),
+            DBError::NotFoundError(ref e) => ::std::fmt::Display::fmt(e, f),
+            DBError::ValidationError(ref e) => ::std::fmt::Display::fmt(e, f),
+            DBError::ConflictError(ref e) => ::std::fmt::Display::fmt(e, f),
+            DBError::GoneError(ref e) => ::std::fmt::Display::fmt(e, f),
             DBError::OtherError(ref e) => ::std::fmt::Display::fmt(e, f),
         }
     }
@@ -112,28 +238,60 @@ pub async fn select_hello(db: web::Data<DB>) -> WebResult<String> {
 
 // USER MANAGEMENT
 
-pub async fn authenticate(db: web::Data<DB>, info: Login) -> WebResult<(Credentials, String)> {
+// request is stored with the log entry, e.g. the ip and user agent
+pub async fn authenticate(db: web::Data<DB>, info: Login, request: serde_json::Value) -> WebResult<(Credentials, String)> {
     build_query!(
         (Credentials, String),
         db,
-        "SELECT success, message, roles FROM authenticate($1, $2);",
-        &[&info.username, &info.password],
+        "SELECT success, message, roles FROM authenticate($1, $2, $3);",
+        &[&info.username, &info.password, &request],
         {|row|
             match row.get(0) {
-                true => Ok((Credentials { username: info.username.clone(), roles: row.get(2)}, row.get(1))),
+                true => Ok((Credentials { username: info.username.clone(), roles: row.get(2), needs_two_factor: false }, row.get(1))),
                 false => Err(DBError::AuthenticationError(row.get(1)))
             }
         }
     )
 }
 
+// returns the token to email, None if there is no active account with that username
+pub async fn request_password_reset(db: web::Data<DB>, username: String, request: serde_json::Value) -> WebResult<Option<String>> {
+    build_query!(
+        Option<String>,
+        db,
+        "SELECT success, message, reset_token FROM request_password_reset($1, $2);",
+        &[&username, &request],
+        {|row|
+            Ok(row.get(2))
+        }
+    )
+}
+
+pub async fn reset_password(db: web::Data<DB>, token: String, info: PasswordReset, request: serde_json::Value) -> WebResult<String> {
+    build_query!(
+        String,
+        db,
+        "SELECT success, message FROM reset_password($1, $2, $3, $4);",
+        &[&token, &info.password, &info.confirm, &request],
+        {|row| {
+            let message: String = row.get(1);
+            match row.get(0) {
+                true => Ok(message),
+                false if message.ends_with("does not exist") => Err(DBError::NotFoundError(message)),
+                false if message.ends_with("expired") => Err(DBError::GoneError(message)),
+                false => Err(DBError::ValidationError(message))
+            }
+        }}
+    )
+}
+
 // returns invitation code
-pub async fn register(db: web::Data<DB>, info: Register) -> WebResult<String> {
+pub async fn register(db: web::Data<DB>, info: Register, request: serde_json::Value) -> WebResult<String> {
     build_query!(
         String,
         db,
-        "SELECT success, message, invitation FROM register($1, $2, $3);",
-         &[&info.username, &info.password, &info.confirm],
+        "SELECT success, message, invitation FROM register($1, $2, $3, $4);",
+         &[&info.username, &info.password, &info.confirm, &request],
          {|row|
             match row.get(0) {
                 true => Ok(row.get(2)),
@@ -149,75 +307,133 @@ pub async fn confirm(db: web::Data<DB>, info: String) -> WebResult<String> {
         db,
         "SELECT success, message FROM confirm($1);",
         &[&info],
-        {|row|
+        {|row| {
+            let message: String = row.get(1);
             match row.get(0) {
-                true => Ok(row.get(1)),
-                false => Err(DBError::AuthenticationError(row.get(1)))
+                true => Ok(message),
+                false if message.ends_with("does not exist") => Err(DBError::NotFoundError(message)),
+                false if message.ends_with("expired") => Err(DBError::GoneError(message)),
+                false => Err(DBError::AuthenticationError(message))
             }
+        }}
+    )
+}
+
+// returns a new invitation code, None if there is no unconfirmed account with that username
+pub async fn resend_confirmation(db: web::Data<DB>, username: String, request: serde_json::Value) -> WebResult<Option<String>> {
+    build_query!(
+        Option<String>,
+        db,
+        "SELECT success, message, invitation_token FROM resend_confirmation($1, $2);",
+        &[&username, &request],
+        {|row|
+            Ok(row.get(2))
         }
     )
 }
 
+// only admins get any rows back
+pub async fn get_users(db: web::Data<DB>, username: String, search: String) -> WebResult<Vec<UserAccount>> {
+    build_query!(
+        Vec<UserAccount>,
+        db,
+        "SELECT id, username, display_name, active, created, roles FROM get_users($1, $2);",
+        &[&username, &search],
+        {|rows|
+            Ok(rows
+            .into_iter()
+            .map(to_user_account)
+            .collect())
+        }
+    )
+}
 
-// ARTICLE MANAGEMENT
+pub async fn get_user(db: web::Data<DB>, username: String, id: i32) -> WebResult<UserAccount> {
+    build_query!(
+        Vec<UserAccount>,
+        db,
+        "SELECT id, username, display_name, active, created, roles FROM get_user($1, $2);",
+        &[&username, &id],
+        {|rows|
+            Ok(rows
+            .into_iter()
+            .map(to_user_account)
+            .collect())
+        }
+    )
+    .and_then(|mut users| match users.pop() {
+        Some(user) => Ok(user),
+        None => Err(BlockingError::Error(DBError::NotFoundError("User does not exist".to_string())))
+    })
+}
 
-#[derive(Serialize, PartialEq, Clone)]
-pub struct Article {
-    pub id: i32,
-    pub headline_cn: String,
-    pub date_created: std::time::SystemTime,
-    pub article_body: String,
-    pub summary: String,
-    pub author: String,
-    pub image: Option<String>,
+pub async fn set_user_role(db: web::Data<DB>, username: String, id: i32, role: i32, grant: bool) -> WebResult<String> {
+    build_query!(
+        String,
+        db,
+        "SELECT success, message FROM set_user_role($1, $2, $3, $4);",
+        &[&username, &id, &role, &grant],
+        user_admin_result
+    )
 }
 
-#[derive(Serialize, PartialEq, Clone)]
-pub struct ArticleSummary {
-    pub id: i32,
-    pub headline_cn: String,
-    pub date_created: std::time::SystemTime,
-    pub summary: String,
-    pub author: String,
-    pub image: Option<String>,
+pub async fn set_display_name(db: web::Data<DB>, username: String, id: i32, display_name: Option<String>) -> WebResult<String> {
+    build_query!(
+        String,
+        db,
+        "SELECT success, message FROM set_display_name($1, $2, $3);",
+        &[&username, &id, &display_name],
+        user_admin_result
+    )
 }
 
-pub fn summarize(article: Article) -> ArticleSummary {
-    ArticleSummary {
-        id: article.id,
-        headline_cn: article.headline_cn,
-        date_created: article.date_created,
-        summary: article.summary,
-        author: article.author,
-        image: article.image,
-    }
+pub async fn set_user_active(db: web::Data<DB>, username: String, id: i32, activate: bool) -> WebResult<String> {
+    build_query!(
+        String,
+        db,
+        "SELECT success, message FROM set_user_active($1, $2, $3);",
+        &[&username, &id, &activate],
+        user_admin_result
+    )
 }
 
-#[derive(Serialize, PartialEq, Clone)]
-pub struct TempArticleSummary {
-    pub id: Uuid,
-    pub headline_cn: Option<String>,
-    pub date_created: std::time::SystemTime,
+// returns the invitation token
+pub async fn create_staff_invitation(db: web::Data<DB>, username: String, info: NewStaffInvitation) -> WebResult<String> {
+    build_query!(
+        String,
+        db,
+        "SELECT success, message, invitation_token FROM create_staff_invitation($1, $2, $3);",
+        &[&username, &info.email, &info.roles],
+        {|row| {
+            let message: String = row.get(1);
+            match row.get(0) {
+                true => Ok(row.get(2)),
+                false if message.ends_with("does not exist") => Err(DBError::NotFoundError(message)),
+                false if message.starts_with("Only") => Err(DBError::AuthenticationError(message)),
+                false if message.ends_with("already exists") => Err(DBError::ConflictError(message)),
+                false => Err(DBError::ValidationError(message))
+            }
+        }}
+    )
 }
 
-pub async fn get_articles(db: web::Data<DB>) -> WebResult<Vec<ArticleSummary>> {
+pub async fn get_staff_invitations(db: web::Data<DB>, username: String) -> WebResult<Vec<StaffInvitation>> {
     build_query!(
-        Vec<ArticleSummary>,
+        Vec<StaffInvitation>,
         db,
-        "SELECT articles.id, headlineCN, dateCreated, articleBody, abstract, users.username, image, users.display_name FROM articles JOIN users ON articles.author = users.id;",
-        &[],
-        {|rows| 
+        "SELECT id, email, roles, invitedBy, dateCreated, dateExpires FROM get_staff_invitations($1);",
+        &[&username],
+        {|rows|
             Ok(rows
             .iter()
             .map(|row| {
-                let display_name: Option<String> = row.get(7);
-                ArticleSummary 
+                StaffInvitation
                     { id: row.get(0)
-                    , headline_cn: row.get(1)
-                    , date_created: row.get(2)
-                    , summary: row.get(4)
-                    , author: display_name.unwrap_or_else(|| row.get(5))
-                    , image: row.get(6)
+                    , email: row.get(1)
+                    , roles: row.get(2)
+                    , invited_by: row.get(3)
+                    , date_created: row.get(4)
+                    , date_expires: row.get(5)
                     }
                 })
             .collect())
@@ -225,101 +441,1358 @@ pub async fn get_articles(db: web::Data<DB>) -> WebResult<Vec<ArticleSummary>> {
     )
 }
 
-pub async fn get_article(db: web::Data<DB>, id: i32) -> WebResult<Article> {
+pub async fn revoke_staff_invitation(db: web::Data<DB>, username: String, id: i32) -> WebResult<String> {
     build_query!(
-        Article,
+        String,
         db,
-        "SELECT articles.id, headlineCN, dateCreated, articleBody, abstract, users.username, image, users.display_name FROM articles JOIN users ON articles.author = users.id WHERE articles.id = $1;",
-         &[&id],
-         {|row| {
-            let display_name: Option<String> = row.get(7);
-            Ok(Article
-                { id: row.get(0)
-                , headline_cn: row.get(1)
-                , date_created: row.get(2)
-                , article_body: row.get(3)
-                , summary: row.get(4)
-                , author: display_name.unwrap_or_else(|| row.get(5))
-                , image: row.get(6)
-                })
+        "SELECT success, message FROM revoke_staff_invitation($1, $2);",
+        &[&username, &id],
+        user_admin_result
+    )
+}
+
+pub async fn accept_staff_invitation(db: web::Data<DB>, token: String, info: AcceptInvitation) -> WebResult<String> {
+    build_query!(
+        String,
+        db,
+        "SELECT success, message FROM accept_staff_invitation($1, $2, $3);",
+        &[&token, &info.password, &info.confirm],
+        {|row| {
+            let message: String = row.get(1);
+            match row.get(0) {
+                true => Ok(message),
+                false if message.ends_with("does not exist") => Err(DBError::NotFoundError(message)),
+                false if message.ends_with("expired") => Err(DBError::GoneError(message)),
+                false if message.ends_with("already exists") => Err(DBError::ConflictError(message)),
+                false => Err(DBError::ValidationError(message))
             }
+        }}
+    )
+}
 
-         }
+fn to_user_account(row: tokio_postgres::row::Row) -> UserAccount {
+    UserAccount
+        { id: row.get(0)
+        , username: row.get(1)
+        , display_name: row.get(2)
+        , active: row.get(3)
+        , created: row.get(4)
+        , roles: row.get(5)
+        }
+}
+
+fn user_admin_result(row: tokio_postgres::row::Row) -> DBResult<String> {
+    let message: String = row.get(1);
+    match row.get(0) {
+        true => Ok(message),
+        false if message.ends_with("does not exist") => Err(DBError::NotFoundError(message)),
+        false if message.starts_with("Only") => Err(DBError::AuthenticationError(message)),
+        false => Err(DBError::ValidationError(message))
+    }
+}
+
+
+// SESSIONS
+
+// returns the token for the auth cookie, and whether the login still needs a two-factor code
+pub async fn create_session(db: web::Data<DB>, username: String, ip: String, user_agent: Option<String>) -> WebResult<(String, bool)> {
+    build_query!(
+        (String, bool),
+        db,
+        "SELECT session_token, pending FROM create_session($1, $2, $3);",
+        &[&username, &ip, &user_agent],
+        {|row|
+            Ok((row.get(0), row.get(1)))
+        }
     )
 }
 
-pub async fn get_temp_article_list(db: web::Data<DB>, username: String) -> WebResult<Vec<TempArticleSummary>> {
+// None if the session has been revoked or has expired, or the user has been deactivated since logging in
+pub async fn session_credentials(db: web::Data<DB>, token: String, ip: String, user_agent: Option<String>) -> WebResult<Option<Credentials>> {
     build_query!(
-        Vec<TempArticleSummary>,
+        Vec<Credentials>,
         db,
-        "SELECT id, headlineCN, dateCreated FROM get_temp_articles($1)", 
-        &[&username],
+        "SELECT username, roles, needs_two_factor FROM session_credentials($1, $2, $3);",
+        &[&token, &ip, &user_agent],
         {|rows|
             Ok(rows
             .iter()
             .map(|row| {
-                TempArticleSummary
-                    { id: row.get(0)
-                    , headline_cn: row.get(1)
-                    , date_created: row.get(2)
+                Credentials
+                    { username: row.get(0)
+                    , roles: row.get(1)
+                    , needs_two_factor: row.get(2)
                     }
                 })
-            .collect())            
+            .collect())
         }
-    )
+    ).map(|mut v| v.pop())
 }
 
-// HELPERS
-
-
-pub async fn test(db: web::Data<DB>) -> WebResult<Article> {
-    build_query!(Article, 
-        db, 
-        "SELECT articles.id, headlineCN, dateCreated, articleBody, abstract, users.username, image, users.display_name FROM articles JOIN users ON articles.author = users.id WHERE articles.id = $1;", 
-        &[&1], 
-        {|row| {
-                let display_name: Option<String> = row.get(7);
-                Ok(Article
+// token is the one from the cookie of the request, so that session can be marked as current
+pub async fn get_sessions(db: web::Data<DB>, username: String, token: Option<String>) -> WebResult<Vec<Session>> {
+    build_query!(
+        Vec<Session>,
+        db,
+        "SELECT id, dateCreated, lastSeen, ip, userAgent, current FROM get_sessions($1, $2);",
+        &[&username, &token],
+        {|rows|
+            Ok(rows
+            .iter()
+            .map(|row| {
+                Session
                     { id: row.get(0)
-                    , headline_cn: row.get(1)
-                    , date_created: row.get(2)
-                    , article_body: row.get(3)
-                    , summary: row.get(4)
-                    , author: display_name.unwrap_or_else(|| row.get(5))
-                    , image: row.get(6)
-                    })
-                } })
+                    , date_created: row.get(1)
+                    , last_seen: row.get(2)
+                    , ip: row.get(3)
+                    , user_agent: row.get(4)
+                    , current: row.get::<_, Option<bool>>(5).unwrap_or(false)
+                    }
+                })
+            .collect())
+        }
+    )
 }
 
-fn get(mut c: DBPool, query: &str, params: &[&(dyn tokio_postgres::types::ToSql + Sync)]) -> DBResult<Vec<tokio_postgres::row::Row>> {
-    c.query(query, params).map_err(|e| DBError::TokioPostgresError(e))
+pub async fn revoke_session(db: web::Data<DB>, username: String, session_id: i32) -> WebResult<String> {
+    build_query!(
+        String,
+        db,
+        "SELECT success, message FROM revoke_session($1, $2);",
+        &[&username, &session_id],
+        user_admin_result
+    )
 }
 
-fn get_row(mut c: DBPool, query: &str, params: &[&(dyn tokio_postgres::types::ToSql + Sync)]) -> DBResult<tokio_postgres::row::Row> {
-    c.query_one(query, params).map_err(|e| DBError::TokioPostgresError(e))
+// keep_token is the session to stay logged in, None to log out everywhere
+pub async fn revoke_sessions(db: web::Data<DB>, username: String, keep_token: Option<String>) -> WebResult<String> {
+    build_query!(
+        String,
+        db,
+        "SELECT success, message FROM revoke_sessions($1, $2);",
+        &[&username, &keep_token],
+        user_admin_result
+    )
 }
 
-fn get_from_row(row: tokio_postgres::row::Row) -> DBResult<String> {
-    row.try_get(0).map_err(|e| DBError::TokioPostgresError(e))
+pub async fn end_session(db: web::Data<DB>, token: String) -> WebResult<()> {
+    build_query!(
+        (),
+        db,
+        "SELECT end_session($1);",
+        &[&token],
+        {|_row|
+            Ok(())
+        }
+    )
 }
 
 
-// SET UP THE DATABASE
-pub fn set_up(mut db: DBPool) -> String {
-    let statements: String = glob("migrations/**/*.sql")
-        .unwrap()
-        .map(|path| fs::read_to_string(path.unwrap()).unwrap())
-        .collect::<Vec<String>>()
-        .concat();
+// TWO-FACTOR AUTHENTICATION
 
-    for path in glob("migrations/**/*.sql").unwrap() {
-        println!("found sql file: {:?}", path.unwrap());
+fn two_factor_error(message: String) -> DBError {
+    if message.ends_with("does not exist") {
+        DBError::NotFoundError(message)
+    } else if message.ends_with("expired") {
+        DBError::GoneError(message)
+    } else if message.ends_with("already exists") {
+        DBError::ConflictError(message)
+    } else if message.starts_with("Only") || message == "Wrong code" {
+        DBError::AuthenticationError(message)
+    } else {
+        DBError::ValidationError(message)
     }
+}
+
+pub async fn get_two_factor(db: web::Data<DB>, username: String) -> WebResult<TwoFactorStatus> {
+    build_query!(
+        TwoFactorStatus,
+        db,
+        "SELECT enabled, required, recoveryCodes FROM get_two_factor($1);",
+        &[&username],
+        {|row|
+            Ok(TwoFactorStatus
+                { enabled: row.get(0)
+                , required: row.get(1)
+                , recovery_codes: row.get(2)
+                })
+        }
+    )
+}
+
+// returns the new secret in base32, it isn't used until confirm_two_factor
+pub async fn start_two_factor(db: web::Data<DB>, username: String) -> WebResult<String> {
+    build_query!(
+        String,
+        db,
+        "SELECT success, message, secret FROM start_two_factor($1);",
+        &[&username],
+        {|row|
+            match row.get(0) {
+                true => Ok(row.get(2)),
+                false => Err(two_factor_error(row.get(1)))
+            }
+        }
+    )
+}
+
+// returns the recovery codes, this is the only time they can be seen
+// token is the session to keep, every other session of the user is ended
+pub async fn confirm_two_factor(db: web::Data<DB>, username: String, code: String, token: Option<String>) -> WebResult<RecoveryCodes> {
+    build_query!(
+        RecoveryCodes,
+        db,
+        "SELECT success, message, codes FROM confirm_two_factor($1, $2, $3);",
+        &[&username, &code, &token],
+        {|row|
+            match row.get(0) {
+                true => Ok(RecoveryCodes { codes: row.get(2) }),
+                false => Err(two_factor_error(row.get(1)))
+            }
+        }
+    )
+}
+
+pub async fn reset_recovery_codes(db: web::Data<DB>, username: String, code: String) -> WebResult<RecoveryCodes> {
+    build_query!(
+        RecoveryCodes,
+        db,
+        "SELECT success, message, codes FROM reset_recovery_codes($1, $2);",
+        &[&username, &code],
+        {|row|
+            match row.get(0) {
+                true => Ok(RecoveryCodes { codes: row.get(2) }),
+                false => Err(two_factor_error(row.get(1)))
+            }
+        }
+    )
+}
+
+pub async fn disable_two_factor(db: web::Data<DB>, username: String, code: String) -> WebResult<String> {
+    build_query!(
+        String,
+        db,
+        "SELECT success, message FROM disable_two_factor($1, $2);",
+        &[&username, &code],
+        {|row|
+            match row.get(0) {
+                true => Ok(row.get(1)),
+                false => Err(two_factor_error(row.get(1)))
+            }
+        }
+    )
+}
+
+// the username of a login waiting for its two-factor code, None if it has timed out
+pub async fn pending_login(db: web::Data<DB>, token: String) -> WebResult<Option<String>> {
+    build_query!(
+        Option<String>,
+        db,
+        "SELECT pending_login($1);",
+        &[&token],
+        {|row|
+            Ok(row.get(0))
+        }
+    )
+}
+
+// request is stored with the log entry, e.g. the ip and user agent
+pub async fn complete_login(db: web::Data<DB>, token: String, code: String, request: serde_json::Value) -> WebResult<String> {
+    build_query!(
+        String,
+        db,
+        "SELECT success, message FROM complete_login($1, $2, $3);",
+        &[&token, &code, &request],
+        {|row|
+            match row.get(0) {
+                true => Ok(row.get(1)),
+                false => Err(two_factor_error(row.get(1)))
+            }
+        }
+    )
+}
+
+pub async fn set_role_two_factor(db: web::Data<DB>, username: String, role: i32, required: bool) -> WebResult<String> {
+    build_query!(
+        String,
+        db,
+        "SELECT success, message FROM set_role_two_factor($1, $2, $3);",
+        &[&username, &role, &required],
+        {|row|
+            match row.get(0) {
+                true => Ok(row.get(1)),
+                false => Err(two_factor_error(row.get(1)))
+            }
+        }
+    )
+}
+
+
+// THROTTLING
+
+// seconds until all of the (scope, key) pairs may try again, 0 if none are blocked
+// when it's 0 the attempt has already been counted as a failure for each pair
+pub async fn throttle_attempt(db: web::Data<DB>, attempts: Vec<(&'static str, String)>, request: serde_json::Value) -> WebResult<i32> {
+    let (scopes, keys): (Vec<&str>, Vec<String>) = attempts.into_iter().unzip();
+    build_query!(
+        i32,
+        db,
+        "SELECT throttle_attempt($1, $2, $3);",
+        &[&scopes, &keys, &request],
+        {|row|
+            Ok(row.get(0))
+        }
+    )
+}
+
+// for attempts that turned out not to be failures
+pub async fn throttle_refund(db: web::Data<DB>, scope: &'static str, key: String) -> WebResult<()> {
+    build_query!(
+        (),
+        db,
+        "SELECT throttle_refund($1, $2);",
+        &[&scope, &key],
+        {|_row|
+            Ok(())
+        }
+    )
+}
+
+pub async fn throttle_success(db: web::Data<DB>, scope: &'static str, key: String) -> WebResult<()> {
+    build_query!(
+        (),
+        db,
+        "SELECT throttle_success($1, $2);",
+        &[&scope, &key],
+        {|_row|
+            Ok(())
+        }
+    )
+}
+
+pub async fn get_throttles(db: web::Data<DB>, username: String) -> WebResult<Vec<Throttle>> {
+    build_query!(
+        Vec<Throttle>,
+        db,
+        "SELECT scope, key, failures, lastFailure, blockedUntil FROM get_throttles($1);",
+        &[&username],
+        {|rows|
+            Ok(rows
+            .iter()
+            .map(|row| {
+                Throttle
+                    { scope: row.get(0)
+                    , key: row.get(1)
+                    , failures: row.get(2)
+                    , last_failure: row.get(3)
+                    , blocked_until: row.get(4)
+                    }
+                })
+            .collect())
+        }
+    )
+}
+
+pub async fn clear_throttle(db: web::Data<DB>, username: String, scope: String, key: String) -> WebResult<String> {
+    build_query!(
+        String,
+        db,
+        "SELECT success, message FROM clear_throttle($1, $2, $3);",
+        &[&username, &scope, &key],
+        user_admin_result
+    )
+}
+
+
+// AUDIT LOG
+
+pub async fn get_logs(db: web::Data<DB>, username: String, filter: LogFilter, limit: i32) -> WebResult<Vec<LogEntry>> {
+    build_query!(
+        Vec<LogEntry>,
+        db,
+        "SELECT id, subject, userId, username, dateCreated, entry, detail FROM get_logs($1, $2, $3, $4, $5, $6, $7, $8);",
+        &[&username, &filter.subject, &filter.user, &filter.since, &filter.until, &filter.search, &filter.after, &limit],
+        {|rows|
+            Ok(rows
+            .iter()
+            .map(|row| {
+                LogEntry
+                    { id: row.get(0)
+                    , subject: row.get(1)
+                    , user_id: row.get(2)
+                    , username: row.get(3)
+                    , date_created: row.get(4)
+                    , entry: row.get(5)
+                    , detail: row.get(6)
+                    }
+                })
+            .collect())
+        }
+    )
+}
+
+
+// ARTICLE MANAGEMENT
+
+#[derive(Serialize, PartialEq, Clone)]
+pub struct Article {
+    pub id: i32,
+    pub headline_cn: String,
+    pub date_created: std::time::SystemTime,
+    pub article_body: String,
+    pub article_html: String,
+    pub summary: String,
+    pub bylines: Vec<Byline>,
+    pub image: Option<String>,
+    pub stats: text::TextStats,
+}
+
+// username is None for guest authors, who have no account
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct Byline {
+    pub name: String,
+    pub bio: Option<String>,
+    pub username: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct BylineInput {
+    pub username: Option<String>,
+    pub guest: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct GuestAuthor {
+    pub id: Option<i32>,
+    pub name: String,
+    pub bio: Option<String>,
+}
+
+#[derive(Serialize, PartialEq, Clone)]
+pub struct AuthorProfile {
+    pub username: String,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar: Option<String>,
+}
+
+// an empty or missing field clears it
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct ProfileUpdate {
+    pub bio: Option<String>,
+    pub avatar: Option<String>,
+}
+
+#[derive(Serialize, PartialEq, Clone)]
+pub struct ArticleSummary {
+    pub id: i32,
+    pub headline_cn: String,
+    pub date_created: std::time::SystemTime,
+    pub summary: String,
+    pub bylines: Vec<Byline>,
+    pub image: Option<String>,
+    pub stats: text::TextStats,
+}
+
+pub fn summarize(article: Article) -> ArticleSummary {
+    ArticleSummary {
+        id: article.id,
+        headline_cn: article.headline_cn,
+        date_created: article.date_created,
+        summary: article.summary,
+        bylines: article.bylines,
+        image: article.image,
+        stats: article.stats,
+    }
+}
+
+#[derive(Serialize, PartialEq, Clone)]
+pub struct TempArticleSummary {
+    pub id: Uuid,
+    pub headline_cn: Option<String>,
+    pub date_created: std::time::SystemTime,
+    pub status: String,
+}
+
+#[derive(Serialize, PartialEq, Clone)]
+pub struct TempArticle {
+    pub id: Uuid,
+    pub headline_cn: Option<String>,
+    pub summary: Option<String>,
+    pub article_body: Option<String>,
+    pub image: Option<String>,
+    pub date_created: std::time::SystemTime,
+    pub date_modified: Option<std::time::SystemTime>,
+    pub version: i32,
+    pub status: String,
+}
+
+#[derive(Serialize, PartialEq, Clone)]
+pub struct QueueItem {
+    pub id: Uuid,
+    pub headline_cn: Option<String>,
+    pub status: String,
+    pub author: String,
+    pub date_modified: std::time::SystemTime,
+}
+
+// kind is publication, review or draft, draft is set for the last two and article_id for publications
+#[derive(Serialize, PartialEq, Clone)]
+pub struct CalendarEvent {
+    pub kind: String,
+    pub date: std::time::SystemTime,
+    pub article_id: Option<i32>,
+    pub draft: Option<Uuid>,
+    pub headline_cn: Option<String>,
+    pub status: String,
+    pub person: Option<String>,
+}
+
+// a missing date clears that deadline
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct Deadlines {
+    pub date_due: Option<std::time::SystemTime>,
+    pub date_review_due: Option<std::time::SystemTime>,
+}
+
+// version is the draft version the edit was based on,
+// fields that are missing or null are left as they are, and an empty image removes it
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct TempArticleUpdate {
+    pub version: i32,
+    pub headline_cn: Option<String>,
+    pub summary: Option<String>,
+    pub article_body: Option<String>,
+    pub image: Option<String>,
+}
+
+pub async fn get_articles(db: web::Data<DB>) -> WebResult<Vec<ArticleSummary>> {
+    build_query!(
+        Vec<ArticleSummary>,
+        db,
+        "SELECT articles.id, headlineCN, dateCreated, wordCount, cjkCharacters, abstract, article_bylines(articles.id), image FROM articles WHERE NOT articles.disabled AND coalesce(articles.datePublished, articles.dateCreated) <= timezone('utc', now());",
+        &[],
+        {|rows| 
+            Ok(rows
+            .iter()
+            .map(to_article_summary)
+            .collect())
+        }
+    )
+}
+
+// columns are id, headlineCN, dateCreated, wordCount, cjkCharacters, abstract, article_bylines(id), image
+// cjkCharacters is NULL until count_text has got to an old article
+fn to_article_summary(row: &tokio_postgres::row::Row) -> ArticleSummary {
+    let cjk_characters: Option<i32> = row.get(4);
+    ArticleSummary
+        { id: row.get(0)
+        , headline_cn: row.get(1)
+        , date_created: row.get(2)
+        , summary: row.get(5)
+        , bylines: to_bylines(row.get(6))
+        , image: row.get(7)
+        , stats: text::from_counts(cjk_characters.unwrap_or(0), row.get(3))
+        }
+}
+
+// the json from article_bylines(), an article always has at least its creator
+fn to_bylines(json: serde_json::Value) -> Vec<Byline> {
+    serde_json::from_value(json).unwrap_or_default()
+}
+
+// disabled articles are reported as gone rather than missing,
+// scheduled articles don't exist until their publish time
+pub async fn get_article(db: web::Data<DB>, id: i32) -> WebResult<Article> {
+    build_query!(
+        Vec<(Article, bool)>,
+        db,
+        "SELECT articles.id, headlineCN, dateCreated, articleBody, abstract, article_bylines(articles.id), image, articles.disabled FROM articles WHERE articles.id = $1 AND coalesce(articles.datePublished, articles.dateCreated) <= timezone('utc', now());",
+         &[&id],
+         {|rows|
+            Ok(rows
+            .iter()
+            .map(|row| {
+                let body: String = row.get(3);
+                (Article
+                    { id: row.get(0)
+                    , headline_cn: row.get(1)
+                    , date_created: row.get(2)
+                    , stats: text::stats(&body)
+                    , article_html: markdown::render(&body)
+                    , article_body: body
+                    , summary: row.get(4)
+                    , bylines: to_bylines(row.get(5))
+                    , image: row.get(6)
+                    }
+                , row.get(7))
+                })
+            .collect())
+         }
+    )
+    .and_then(|mut articles| match articles.pop() {
+        Some((article, false)) => Ok(article),
+        Some((_, true)) => Err(BlockingError::Error(DBError::GoneError("Article has been taken down".to_string()))),
+        None => Err(BlockingError::Error(DBError::NotFoundError("Article does not exist".to_string())))
+    })
+}
+
+pub async fn set_article_disabled(db: web::Data<DB>, username: String, id: i32, disable: bool, reason: Option<String>) -> WebResult<String> {
+    build_query!(
+        String,
+        db,
+        "SELECT success, message FROM set_article_disabled($1, $2, $3, $4);",
+        &[&username, &id, &disable, &reason],
+        {|row| {
+            let message: String = row.get(1);
+            match row.get(0) {
+                true => Ok(message),
+                false if message.ends_with("does not exist") => Err(DBError::NotFoundError(message)),
+                false => Err(DBError::AuthenticationError(message))
+            }
+        }}
+    )
+}
+
+pub async fn schedule_article(db: web::Data<DB>, username: String, id: i32, publish_at: std::time::SystemTime) -> WebResult<String> {
+    build_query!(
+        String,
+        db,
+        "SELECT success, message FROM schedule_article($1, $2, $3);",
+        &[&username, &id, &publish_at],
+        {|row| {
+            let message: String = row.get(1);
+            match row.get(0) {
+                true => Ok(message),
+                false if message.ends_with("does not exist") => Err(DBError::NotFoundError(message)),
+                false => Err(DBError::AuthenticationError(message))
+            }
+        }}
+    )
+}
+
+// returns when the next scheduled article is due, if any
+pub async fn record_published_articles(db: web::Data<DB>) -> WebResult<Option<std::time::SystemTime>> {
+    build_query!(
+        Option<std::time::SystemTime>,
+        db,
+        "SELECT record_published_articles();",
+        &[],
+        {|row|
+            Ok(row.get(0))
+        }
+    )
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct ArticleUpdate {
+    pub headline_cn: String,
+    pub summary: String,
+    pub article_body: String,
+    pub image: Option<String>,
+}
+
+#[derive(Serialize, PartialEq, Clone)]
+pub struct ArticleRevision {
+    pub id: i32,
+    pub headline_cn: String,
+    pub word_count: i32,
+    pub editor: Option<String>,
+    pub date_created: std::time::SystemTime,
+}
+
+#[derive(Serialize, PartialEq, Clone)]
+pub struct RevisionContent {
+    pub id: i32,
+    pub headline_cn: String,
+    pub summary: String,
+    pub article_body: String,
+    pub image: Option<String>,
+}
+
+// returns the id of the new revision
+pub async fn update_article(db: web::Data<DB>, username: String, id: i32, info: ArticleUpdate) -> WebResult<i32> {
+    let stats = text::stats(&info.article_body);
+    build_query!(
+        i32,
+        db,
+        "SELECT success, message, revision_id FROM update_article($1, $2, $3, $4, $5, $6, $7, $8);",
+        &[&username, &id, &info.headline_cn, &info.summary, &info.article_body, &info.image, &stats.word_count, &stats.cjk_characters],
+        revision_result
+    )
+}
+
+pub async fn get_article_revisions(db: web::Data<DB>, username: String, id: i32) -> WebResult<Vec<ArticleRevision>> {
+    build_query!(
+        Vec<ArticleRevision>,
+        db,
+        "SELECT id, headlineCN, wordCount, editor, dateCreated FROM get_article_revisions($1, $2);",
+        &[&username, &id],
+        {|rows|
+            Ok(rows
+            .iter()
+            .map(|row| {
+                ArticleRevision
+                    { id: row.get(0)
+                    , headline_cn: row.get(1)
+                    , word_count: row.get(2)
+                    , editor: row.get(3)
+                    , date_created: row.get(4)
+                    }
+                })
+            .collect())
+        }
+    )
+}
+
+pub async fn get_article_revision(db: web::Data<DB>, username: String, id: i32, revision: i32) -> WebResult<RevisionContent> {
+    build_query!(
+        Vec<RevisionContent>,
+        db,
+        "SELECT id, headlineCN, abstract, articleBody, image FROM article_revisions WHERE article = $1 AND id = $2 AND can_read_revisions($3, $1);",
+        &[&id, &revision, &username],
+        {|rows|
+            Ok(rows
+            .iter()
+            .map(|row| {
+                RevisionContent
+                    { id: row.get(0)
+                    , headline_cn: row.get(1)
+                    , summary: row.get(2)
+                    , article_body: row.get(3)
+                    , image: row.get(4)
+                    }
+                })
+            .collect())
+        }
+    )
+    .and_then(|mut revisions| revisions.pop()
+        .ok_or_else(|| BlockingError::Error(DBError::NotFoundError("Revision does not exist".to_string()))))
+}
+
+// returns the id of the new revision created by the restore
+pub async fn restore_article_revision(db: web::Data<DB>, username: String, id: i32, revision: i32) -> WebResult<i32> {
+    build_query!(
+        i32,
+        db,
+        "SELECT success, message, revision_id FROM restore_article_revision($1, $2, $3);",
+        &[&username, &id, &revision],
+        revision_result
+    )
+}
+
+fn revision_result(row: tokio_postgres::row::Row) -> DBResult<i32> {
+    let message: String = row.get(1);
+    match row.get(0) {
+        true => Ok(row.get(2)),
+        false if message.ends_with("does not exist") => Err(DBError::NotFoundError(message)),
+        false => Err(DBError::AuthenticationError(message))
+    }
+}
+
+pub async fn get_temp_article_list(db: web::Data<DB>, username: String) -> WebResult<Vec<TempArticleSummary>> {
+    build_query!(
+        Vec<TempArticleSummary>,
+        db,
+        "SELECT id, headlineCN, dateCreated, status FROM get_temp_articles($1)", 
+        &[&username],
+        {|rows|
+            Ok(rows
+            .iter()
+            .map(|row| {
+                TempArticleSummary
+                    { id: row.get(0)
+                    , headline_cn: row.get(1)
+                    , date_created: row.get(2)
+                    , status: row.get(3)
+                    }
+                })
+            .collect())            
+        }
+    )
+}
+
+pub async fn create_temp_article(db: web::Data<DB>, username: String) -> WebResult<Uuid> {
+    build_query!(
+        Uuid,
+        db,
+        "SELECT create_temp_article($1);",
+        &[&username],
+        {|row|
+            Ok(row.get(0))
+        }
+    )
+}
+
+pub async fn get_temp_article(db: web::Data<DB>, username: String, id: Uuid) -> WebResult<TempArticle> {
+    build_query!(
+        Vec<TempArticle>,
+        db,
+        "SELECT id, headlineCN, abstract, articleBody, image, dateCreated, dateModified, version, status FROM get_temp_article($1, $2);",
+        &[&username, &id],
+        {|rows|
+            Ok(rows
+            .iter()
+            .map(|row| {
+                TempArticle
+                    { id: row.get(0)
+                    , headline_cn: row.get(1)
+                    , summary: row.get(2)
+                    , article_body: row.get(3)
+                    , image: row.get(4)
+                    , date_created: row.get(5)
+                    , date_modified: row.get(6)
+                    , version: row.get(7)
+                    , status: row.get(8)
+                    }
+                })
+            .collect())
+        }
+    )
+    .and_then(|mut drafts| drafts.pop()
+        .ok_or_else(|| BlockingError::Error(DBError::NotFoundError("Draft does not exist".to_string()))))
+}
+
+// returns the current version of the draft
+pub async fn update_temp_article(db: web::Data<DB>, username: String, id: Uuid, info: TempArticleUpdate) -> WebResult<i32> {
+    // images are uploaded separately, only their filename is stored
+    if let Some(image) = &info.image {
+        if image.starts_with("data:") || image.contains('/') {
+            return Err(BlockingError::Error(DBError::ValidationError("Image must be an uploaded filename".to_string())));
+        }
+    }
+    let stats = info.article_body.as_ref().map(|body| text::stats(body));
+    let word_count = stats.map(|stats| stats.word_count);
+    let cjk_characters = stats.map(|stats| stats.cjk_characters);
+    build_query!(
+        i32,
+        db,
+        "SELECT success, message, conflict, current_version FROM update_temp_article($1, $2, $3, $4, $5, $6, $7, $8, $9);",
+        &[&username, &id, &info.version, &info.headline_cn, &info.summary, &info.article_body, &info.image, &word_count, &cjk_characters],
+        {|row|
+            match (row.get(0), row.get(2)) {
+                (true, _) => Ok(row.get(3)),
+                (false, true) => Err(DBError::ConflictError(row.get(1))),
+                (false, false) => {
+                    let message: String = row.get(1);
+                    if message.ends_with("does not exist") {
+                        Err(DBError::NotFoundError(message))
+                    } else {
+                        Err(DBError::ValidationError(message))
+                    }
+                }
+            }
+        }
+    )
+}
+
+pub async fn delete_temp_article(db: web::Data<DB>, username: String, id: Uuid) -> WebResult<String> {
+    build_query!(
+        String,
+        db,
+        "SELECT success, message FROM delete_temp_article($1, $2);",
+        &[&username, &id],
+        {|row| {
+            let message: String = row.get(1);
+            match row.get(0) {
+                true => Ok(message),
+                false if message.ends_with("does not exist") => Err(DBError::NotFoundError(message)),
+                false => Err(DBError::ValidationError(message))
+            }
+        }}
+    )
+}
+
+// returns the id of the newly created article, which stays hidden until publish_at if that is given
+pub async fn publish_temp_article(db: web::Data<DB>, username: String, id: Uuid, publish_at: Option<std::time::SystemTime>) -> WebResult<i32> {
+    build_query!(
+        i32,
+        db,
+        "SELECT success, message, article_id FROM publish_temp_article($1, $2, $3);",
+        &[&username, &id, &publish_at],
+        {|row| {
+            let message: String = row.get(1);
+            match row.get(0) {
+                true => Ok(row.get(2)),
+                false if message == "Draft does not exist" => Err(DBError::NotFoundError(message)),
+                false => Err(DBError::ValidationError(message))
+            }
+        }}
+    )
+}
+
+// BYLINES
+
+pub async fn set_article_bylines(db: web::Data<DB>, username: String, id: i32, bylines: Vec<BylineInput>) -> WebResult<String> {
+    let bylines = serde_json::to_value(bylines).unwrap_or_default();
+    build_query!(
+        String,
+        db,
+        "SELECT success, message FROM set_article_bylines($1, $2, $3);",
+        &[&username, &id, &bylines],
+        {|row| {
+            let message: String = row.get(1);
+            match row.get(0) {
+                true => Ok(message),
+                false if message.ends_with("does not exist") => Err(DBError::NotFoundError(message)),
+                false if message.starts_with("Not allowed") => Err(DBError::AuthenticationError(message)),
+                false => Err(DBError::ValidationError(message))
+            }
+        }}
+    )
+}
+
+pub async fn get_guest_authors(db: web::Data<DB>, username: String) -> WebResult<Vec<GuestAuthor>> {
+    build_query!(
+        Vec<GuestAuthor>,
+        db,
+        "SELECT id, name, bio FROM get_guest_authors($1);",
+        &[&username],
+        {|rows|
+            Ok(rows
+            .iter()
+            .map(|row| {
+                GuestAuthor
+                    { id: row.get(0)
+                    , name: row.get(1)
+                    , bio: row.get(2)
+                    }
+                })
+            .collect())
+        }
+    )
+}
+
+// creates the guest author if info.id is None, returns the id
+pub async fn save_guest_author(db: web::Data<DB>, username: String, info: GuestAuthor) -> WebResult<i32> {
+    build_query!(
+        i32,
+        db,
+        "SELECT success, message, saved_id FROM save_guest_author($1, $2, $3, $4);",
+        &[&username, &info.id, &info.name, &info.bio],
+        {|row| {
+            let message: String = row.get(1);
+            match row.get(0) {
+                true => Ok(row.get(2)),
+                false if message.ends_with("does not exist") => Err(DBError::NotFoundError(message)),
+                false if message.starts_with("Only") => Err(DBError::AuthenticationError(message)),
+                false => Err(DBError::ValidationError(message))
+            }
+        }}
+    )
+}
+
+// AUTHOR PROFILES
+
+pub async fn get_author_profile(db: web::Data<DB>, author: String) -> WebResult<AuthorProfile> {
+    build_query!(
+        Vec<AuthorProfile>,
+        db,
+        "SELECT username, display_name, bio, avatar FROM get_author_profile($1);",
+        &[&author],
+        {|rows|
+            Ok(rows
+            .iter()
+            .map(|row| {
+                AuthorProfile
+                    { username: row.get(0)
+                    , display_name: row.get(1)
+                    , bio: row.get(2)
+                    , avatar: row.get(3)
+                    }
+                })
+            .collect())
+        }
+    )
+    .and_then(|mut profiles| profiles.pop()
+        .ok_or_else(|| BlockingError::Error(DBError::NotFoundError("Author does not exist".to_string()))))
+}
+
+// newest first, after is the id of the last article already shown
+pub async fn get_author_articles(db: web::Data<DB>, author: String, after: Option<i32>, limit: i32) -> WebResult<Vec<ArticleSummary>> {
+    build_query!(
+        Vec<ArticleSummary>,
+        db,
+        "SELECT id, headlineCN, dateCreated, wordCount, cjkCharacters, abstract, bylines, image FROM get_author_articles($1, $2, $3);",
+        &[&author, &after, &limit],
+        {|rows|
+            Ok(rows
+            .iter()
+            .map(to_article_summary)
+            .collect())
+        }
+    )
+}
+
+pub async fn set_author_profile(db: web::Data<DB>, username: String, profile: ProfileUpdate) -> WebResult<String> {
+    // same as article images, the avatar is uploaded first
+    if let Some(avatar) = &profile.avatar {
+        if avatar.starts_with("data:") || avatar.contains('/') {
+            return Err(BlockingError::Error(DBError::ValidationError("Avatar must be an uploaded filename".to_string())));
+        }
+    }
+    build_query!(
+        String,
+        db,
+        "SELECT success, message FROM set_author_profile($1, $2, $3);",
+        &[&username, &profile.bio, &profile.avatar],
+        {|row| {
+            let message: String = row.get(1);
+            match row.get(0) {
+                true => Ok(message),
+                false => Err(DBError::AuthenticationError(message))
+            }
+        }}
+    )
+}
+
+// WORKFLOW
+
+// action is one of submit, start_review, approve or request_changes, returns the new status
+pub async fn transition_temp_article(db: web::Data<DB>, username: String, id: Uuid, action: &'static str, note: Option<String>) -> WebResult<String> {
+    build_query!(
+        String,
+        db,
+        "SELECT success, message, status FROM transition_temp_article($1, $2, $3, $4);",
+        &[&username, &id, &action, &note],
+        {|row| {
+            let message: String = row.get(1);
+            match row.get(0) {
+                true => Ok(row.get(2)),
+                false if message.ends_with("does not exist") => Err(DBError::NotFoundError(message)),
+                false if message.starts_with("Only") || message.ends_with("someone else") => Err(DBError::AuthenticationError(message)),
+                false => Err(DBError::ConflictError(message))
+            }
+        }}
+    )
+}
+
+// queue is one of author, reviewer or publisher
+pub async fn get_workflow_queue(db: web::Data<DB>, username: String, queue: &'static str) -> WebResult<Vec<QueueItem>> {
+    build_query!(
+        Vec<QueueItem>,
+        db,
+        "SELECT id, headlineCN, status, author, dateModified FROM get_workflow_queue($1, $2);",
+        &[&username, &queue],
+        {|rows|
+            Ok(rows
+            .iter()
+            .map(|row| {
+                QueueItem
+                    { id: row.get(0)
+                    , headline_cn: row.get(1)
+                    , status: row.get(2)
+                    , author: row.get(3)
+                    , date_modified: row.get(4)
+                    }
+                })
+            .collect())
+        }
+    )
+}
+
+// CALENDAR
+
+pub async fn get_calendar(db: web::Data<DB>, username: String, since: std::time::SystemTime, until: std::time::SystemTime) -> WebResult<Vec<CalendarEvent>> {
+    build_query!(
+        Vec<CalendarEvent>,
+        db,
+        "SELECT kind, event_date, article_id, draft, headline, status, person FROM get_calendar($1, $2, $3);",
+        &[&username, &since, &until],
+        {|rows|
+            Ok(rows
+            .iter()
+            .map(|row| {
+                CalendarEvent
+                    { kind: row.get(0)
+                    , date: row.get(1)
+                    , article_id: row.get(2)
+                    , draft: row.get(3)
+                    , headline_cn: row.get(4)
+                    , status: row.get(5)
+                    , person: row.get(6)
+                    }
+                })
+            .collect())
+        }
+    )
+}
+
+pub async fn set_draft_deadlines(db: web::Data<DB>, username: String, id: Uuid, deadlines: Deadlines) -> WebResult<String> {
+    build_query!(
+        String,
+        db,
+        "SELECT success, message FROM set_draft_deadlines($1, $2, $3, $4);",
+        &[&username, &id, &deadlines.date_due, &deadlines.date_review_due],
+        {|row| {
+            let message: String = row.get(1);
+            match row.get(0) {
+                true => Ok(message),
+                false if message.ends_with("does not exist") => Err(DBError::NotFoundError(message)),
+                false => Err(DBError::AuthenticationError(message))
+            }
+        }}
+    )
+}
+
+// only the hash is stored, so token is None unless it was created by this call
+pub struct CalendarToken {
+    pub token: Option<String>,
+    pub date_created: std::time::SystemTime,
+}
+
+// reset replaces the token, so calendar apps subscribed with the old one stop getting updates
+pub async fn get_calendar_token(db: web::Data<DB>, username: String, reset: bool) -> WebResult<CalendarToken> {
+    build_query!(
+        CalendarToken,
+        db,
+        "SELECT success, message, feed_token, date_created FROM get_calendar_token($1, $2);",
+        &[&username, &reset],
+        {|row| {
+            let message: String = row.get(1);
+            match row.get(0) {
+                true => Ok(CalendarToken { token: row.get(2), date_created: row.get(3) }),
+                false => Err(DBError::AuthenticationError(message))
+            }
+        }}
+    )
+}
+
+// None if the token is unknown or its user has been deactivated
+pub async fn calendar_user(db: web::Data<DB>, token: String) -> WebResult<Option<String>> {
+    build_query!(
+        Option<String>,
+        db,
+        "SELECT calendar_user($1);",
+        &[&token],
+        {|row|
+            Ok(row.get(0))
+        }
+    )
+}
+
+// COMMENTS
+
+// anchors are char offsets into the draft body, cleared if an edit removed the text
+#[derive(Serialize, PartialEq, Clone)]
+pub struct DraftComment {
+    pub id: i32,
+    pub parent: Option<i32>,
+    pub author: String,
+    pub body: String,
+    pub anchor_start: Option<i32>,
+    pub anchor_end: Option<i32>,
+    pub anchor_text: Option<String>,
+    pub resolved: bool,
+    pub date_created: std::time::SystemTime,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct NewComment {
+    pub body: String,
+    pub parent: Option<i32>,
+    pub anchor_start: Option<usize>,
+    pub anchor_end: Option<usize>,
+}
+
+pub async fn get_draft_comments(db: web::Data<DB>, username: String, draft: Uuid) -> WebResult<Vec<DraftComment>> {
+    build_query!(
+        Vec<DraftComment>,
+        db,
+        "SELECT id, parent, author, body, anchorStart, anchorEnd, anchorText, resolved, dateCreated FROM get_draft_comments($1, $2);",
+        &[&username, &draft],
+        {|rows|
+            Ok(rows
+            .iter()
+            .map(|row| {
+                DraftComment
+                    { id: row.get(0)
+                    , parent: row.get(1)
+                    , author: row.get(2)
+                    , body: row.get(3)
+                    , anchor_start: row.get(4)
+                    , anchor_end: row.get(5)
+                    , anchor_text: row.get(6)
+                    , resolved: row.get(7)
+                    , date_created: row.get(8)
+                    }
+                })
+            .collect())
+        }
+    )
+}
+
+// returns the id of the new comment
+pub async fn add_draft_comment(db: web::Data<DB>, username: String, draft: Uuid, info: NewComment, anchor: Option<anchor::Anchor>) -> WebResult<i32> {
+    let (start, end, text, prefix, suffix) = match anchor {
+        Some(a) => (Some(a.start as i32), Some(a.end as i32), Some(a.text), Some(a.prefix), Some(a.suffix)),
+        None => (None, None, None, None, None)
+    };
+    build_query!(
+        i32,
+        db,
+        "SELECT success, message, comment_id FROM add_draft_comment($1, $2, $3, $4, $5, $6, $7, $8, $9);",
+        &[&username, &draft, &info.parent, &info.body, &start, &end, &text, &prefix, &suffix],
+        {|row|
+            match row.get(0) {
+                true => Ok(row.get(2)),
+                false => Err(DBError::NotFoundError(row.get(1)))
+            }
+        }
+    )
+}
+
+pub async fn set_comment_resolved(db: web::Data<DB>, username: String, draft: Uuid, comment: i32, resolve: bool) -> WebResult<String> {
+    build_query!(
+        String,
+        db,
+        "SELECT success, message FROM set_comment_resolved($1, $2, $3, $4);",
+        &[&username, &draft, &comment, &resolve],
+        {|row|
+            match row.get(0) {
+                true => Ok(row.get(1)),
+                false => Err(DBError::NotFoundError(row.get(1)))
+            }
+        }
+    )
+}
+
+// moves comment anchors to follow the text they point at after the draft body changed
+pub async fn reanchor_draft_comments(db: web::Data<DB>, draft: Uuid, body: String) -> WebResult<()> {
+    let db2 = db.clone();
+    let anchors = build_query!(
+        Vec<(i32, anchor::Anchor)>,
+        db,
+        "SELECT id, anchorStart, anchorEnd, anchorText, coalesce(anchorPrefix, ''), coalesce(anchorSuffix, '') FROM draft_comments WHERE draft = $1 AND anchorStart IS NOT NULL AND parent IS NULL;",
+        &[&draft],
+        {|rows|
+            Ok(rows
+            .iter()
+            .map(|row| {
+                let start: i32 = row.get(1);
+                let end: i32 = row.get(2);
+                (row.get(0), anchor::Anchor
+                    { start: start as usize
+                    , end: end as usize
+                    , text: row.get(3)
+                    , prefix: row.get(4)
+                    , suffix: row.get(5)
+                    })
+                })
+            .collect())
+        }
+    )?;
+
+    let mut ids: Vec<i32> = Vec::new();
+    let mut starts: Vec<Option<i32>> = Vec::new();
+    let mut ends: Vec<Option<i32>> = Vec::new();
+    let mut prefixes: Vec<Option<String>> = Vec::new();
+    let mut suffixes: Vec<Option<String>> = Vec::new();
+    for (id, old) in anchors {
+        let new = anchor::reanchor(&body, &old);
+        if new.as_ref() != Some(&old) {
+            ids.push(id);
+            starts.push(new.as_ref().map(|a| a.start as i32));
+            ends.push(new.as_ref().map(|a| a.end as i32));
+            prefixes.push(new.as_ref().map(|a| a.prefix.clone()));
+            suffixes.push(new.map(|a| a.suffix));
+        }
+    }
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    // orphaned comments keep anchorText so reviewers can still see what they referred to
+    let db = db2;
+    build_query!(
+        Vec<()>,
+        db,
+        "UPDATE draft_comments SET anchorStart = u.s, anchorEnd = u.e, anchorPrefix = u.p, anchorSuffix = u.x FROM unnest($1::INTEGER[], $2::INTEGER[], $3::INTEGER[], $4::TEXT[], $5::TEXT[]) AS u(id, s, e, p, x) WHERE draft_comments.id = u.id;",
+        &[&ids, &starts, &ends, &prefixes, &suffixes],
+        {|_rows|
+            Ok(Vec::new())
+        }
+    )
+    .map(|_| ())
+}
+
+// HELPERS
+
+
+pub async fn test(db: web::Data<DB>) -> WebResult<Article> {
+    build_query!(Article, 
+        db, 
+        "SELECT articles.id, headlineCN, dateCreated, articleBody, abstract, article_bylines(articles.id), image FROM articles WHERE articles.id = $1;", 
+        &[&1], 
+        {|row| {
+                let body: String = row.get(3);
+                Ok(Article
+                    { id: row.get(0)
+                    , headline_cn: row.get(1)
+                    , date_created: row.get(2)
+                    , stats: text::stats(&body)
+                    , article_html: markdown::render(&body)
+                    , article_body: body
+                    , summary: row.get(4)
+                    , bylines: to_bylines(row.get(5))
+                    , image: row.get(6)
+                    })
+                } })
+}
+
+fn get(mut c: DBPool, query: &str, params: &[&(dyn tokio_postgres::types::ToSql + Sync)]) -> DBResult<Vec<tokio_postgres::row::Row>> {
+    c.query(query, params).map_err(|e| DBError::TokioPostgresError(e))
+}
+
+fn get_row(mut c: DBPool, query: &str, params: &[&(dyn tokio_postgres::types::ToSql + Sync)]) -> DBResult<tokio_postgres::row::Row> {
+    c.query_one(query, params).map_err(|e| DBError::TokioPostgresError(e))
+}
+
+fn get_from_row(row: tokio_postgres::row::Row) -> DBResult<String> {
+    row.try_get(0).map_err(|e| DBError::TokioPostgresError(e))
+}
+
+
+// SET UP THE DATABASE
+pub fn set_up(mut db: DBPool) -> String {
+    let statements: String = glob("migrations/**/*.sql")
+        .unwrap()
+        .map(|path| fs::read_to_string(path.unwrap()).unwrap())
+        .collect::<Vec<String>>()
+        .concat();
+
+    for path in glob("migrations/**/*.sql").unwrap() {
+        println!("found sql file: {:?}", path.unwrap());
+    }
+
+    println!("executing sql...");
 
-    println!("executing sql...");
-
     match db.batch_execute(statements.as_str()) {
         Ok(_) => "database is set up".to_string(),
         Err(e) => e.to_string()
     }
 }
+
+// text saved before articles stored cjkCharacters is counted once, after the migrations have run,
+// on later startups there is nothing left to count
+// counting is done here rather than in a migration so it matches text::stats exactly
+pub fn count_text(mut db: DBPool) -> Result<u64, tokio_postgres::Error> {
+    Ok(count_table::<i32>(&mut db, "articles")?
+        + count_table::<Uuid>(&mut db, "temp_articles")?
+        + count_table::<i32>(&mut db, "article_revisions")?)
+}
+
+// ids keep their own type, so each update is a primary key lookup
+fn count_table<T>(db: &mut DBPool, table: &str) -> Result<u64, tokio_postgres::Error>
+where T: for<'a> tokio_postgres::types::FromSql<'a> + tokio_postgres::types::ToSql + Sync {
+    let rows = db.query(format!("SELECT id, articleBody FROM {} WHERE cjkCharacters IS NULL AND articleBody IS NOT NULL;", table).as_str(), &[])?;
+
+    let update = format!("UPDATE {} SET wordCount = $1, cjkCharacters = $2 WHERE id = $3;", table);
+    for row in &rows {
+        let id: T = row.get(0);
+        let body: String = row.get(1);
+        let stats = text::stats(&body);
+        db.execute(update.as_str(), &[&stats.word_count, &stats.cjk_characters, &id])?;
+    }
+    Ok(rows.len() as u64)
+}
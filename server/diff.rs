@@ -0,0 +1,179 @@
+use serde::{Serialize};
+
+// above this many characters a changed block is shown as a whole delete and insert,
+// the diff takes up to (characters)² steps when the texts have little in common
+const MAX_CHAR_DIFF: usize = 5_000;
+
+#[derive(Serialize, PartialEq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct Chunk {
+    pub op: Op,
+    pub text: String,
+}
+
+// diffs line by line, then refines each replaced block character by character,
+// so text without spaces (e.g. Chinese) still gets a precise diff
+pub fn diff_text(old: &str, new: &str) -> Vec<Chunk> {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+
+    let mut chunks = Vec::new();
+    let mut deleted = String::new();
+    let mut inserted = String::new();
+
+    for (op, i, j) in myers(&old_lines, &new_lines) {
+        match op {
+            Op::Delete => deleted.push_str(old_lines[i]),
+            Op::Insert => inserted.push_str(new_lines[j]),
+            Op::Equal => {
+                flush_block(&mut chunks, &mut deleted, &mut inserted);
+                push(&mut chunks, Op::Equal, old_lines[i]);
+            }
+        }
+    }
+    flush_block(&mut chunks, &mut deleted, &mut inserted);
+
+    chunks
+}
+
+fn flush_block(chunks: &mut Vec<Chunk>, deleted: &mut String, inserted: &mut String) {
+    let old_chars: Vec<char> = deleted.chars().collect();
+    let new_chars: Vec<char> = inserted.chars().collect();
+    if old_chars.is_empty() || new_chars.is_empty() || old_chars.len() + new_chars.len() > MAX_CHAR_DIFF {
+        push(chunks, Op::Delete, deleted);
+        push(chunks, Op::Insert, inserted);
+    } else {
+        for (op, i, j) in myers(&old_chars, &new_chars) {
+            let c = if op == Op::Insert { new_chars[j] } else { old_chars[i] };
+            push(chunks, op, c.encode_utf8(&mut [0; 4]));
+        }
+    }
+    deleted.clear();
+    inserted.clear();
+}
+
+// appends to the last chunk when the operation is the same
+fn push(chunks: &mut Vec<Chunk>, op: Op, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    match chunks.last_mut() {
+        Some(last) if last.op == op => last.text.push_str(text),
+        _ => chunks.push(Chunk { op, text: text.to_string() })
+    }
+}
+
+// Myers' O(ND) diff in linear space, returns (op, index into a, index into b) in order
+fn myers<T: PartialEq>(a: &[T], b: &[T]) -> Vec<(Op, usize, usize)> {
+    let offset = a.len() + b.len() + 1;
+    let mut search = Myers {
+        a,
+        b,
+        offset: offset as isize,
+        forward: vec![0; 2 * offset + 1],
+        backward: vec![0; 2 * offset + 1],
+        edits: Vec::with_capacity(a.len() + b.len()),
+    };
+    search.conquer(0, a.len(), 0, b.len());
+    search.edits
+}
+
+// forward and backward hold the furthest x reached on each diagonal k, at index offset + k
+struct Myers<'a, T> {
+    a: &'a [T],
+    b: &'a [T],
+    offset: isize,
+    forward: Vec<isize>,
+    backward: Vec<isize>,
+    edits: Vec<(Op, usize, usize)>,
+}
+
+impl<'a, T: PartialEq> Myers<'a, T> {
+    // diffs a[a_lo..a_hi] against b[b_lo..b_hi] by splitting it at the middle of its shortest edit
+    fn conquer(&mut self, mut a_lo: usize, mut a_hi: usize, mut b_lo: usize, mut b_hi: usize) {
+        while a_lo < a_hi && b_lo < b_hi && self.a[a_lo] == self.b[b_lo] {
+            self.edits.push((Op::Equal, a_lo, b_lo));
+            a_lo += 1;
+            b_lo += 1;
+        }
+        let mut suffix = 0;
+        while a_lo < a_hi && b_lo < b_hi && self.a[a_hi - 1] == self.b[b_hi - 1] {
+            a_hi -= 1;
+            b_hi -= 1;
+            suffix += 1;
+        }
+
+        if a_lo == a_hi {
+            self.edits.extend((b_lo..b_hi).map(|j| (Op::Insert, a_lo, j)));
+        } else if b_lo == b_hi {
+            self.edits.extend((a_lo..a_hi).map(|i| (Op::Delete, i, b_lo)));
+        } else {
+            let (x, y) = self.middle_snake(a_lo, a_hi, b_lo, b_hi);
+            self.conquer(a_lo, x, b_lo, y);
+            self.conquer(x, a_hi, y, b_hi);
+        }
+
+        self.edits.extend((0..suffix).map(|s| (Op::Equal, a_hi + s, b_hi + s)));
+    }
+
+    // searches from both ends until the paths meet, returns where the overlapping snake starts,
+    // both ranges are non-empty and differ at both ends so this is never the start or end
+    fn middle_snake(&mut self, a_lo: usize, a_hi: usize, b_lo: usize, b_hi: usize) -> (usize, usize) {
+        let n = (a_hi - a_lo) as isize;
+        let m = (b_hi - b_lo) as isize;
+        let delta = n - m;
+        let odd = delta % 2 != 0;
+        let o = self.offset;
+        let (f, r) = (&mut self.forward, &mut self.backward);
+        f[(o + 1) as usize] = 0;
+        r[(o + 1) as usize] = 0;
+
+        for d in 0..=(n + m + 1) / 2 {
+            for k in (-d..=d).step_by(2) {
+                let mut x = if k == -d || (k != d && f[(o + k - 1) as usize] < f[(o + k + 1) as usize]) {
+                    f[(o + k + 1) as usize]
+                } else {
+                    f[(o + k - 1) as usize] + 1
+                };
+                let (start_x, start_y) = (x, x - k);
+                let mut y = x - k;
+                while x < n && y < m && self.a[a_lo + x as usize] == self.b[b_lo + y as usize] {
+                    x += 1;
+                    y += 1;
+                }
+                f[(o + k) as usize] = x;
+                // the backward path on the same diagonal is k' = delta - k
+                if odd && (k - delta).abs() < d && x + r[(o + delta - k) as usize] >= n {
+                    return (a_lo + start_x as usize, b_lo + start_y as usize);
+                }
+            }
+
+            // x and y count back from a_hi and b_hi
+            for k in (-d..=d).step_by(2) {
+                let mut x = if k == -d || (k != d && r[(o + k - 1) as usize] < r[(o + k + 1) as usize]) {
+                    r[(o + k + 1) as usize]
+                } else {
+                    r[(o + k - 1) as usize] + 1
+                };
+                let mut y = x - k;
+                while x < n && y < m && self.a[a_hi - 1 - x as usize] == self.b[b_hi - 1 - y as usize] {
+                    x += 1;
+                    y += 1;
+                }
+                r[(o + k) as usize] = x;
+                if !odd && (k - delta).abs() <= d && x + f[(o + delta - k) as usize] >= n {
+                    return (a_hi - x as usize, b_hi - y as usize);
+                }
+            }
+        }
+
+        unreachable!("the forward and backward paths always meet")
+    }
+}
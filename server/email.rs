@@ -16,8 +16,30 @@ pub fn create_email(confirmation_url: String, mail_domain: String, recipient: St
         from: format!("Admin <confirmation@{}>", mail_domain),
         to: recipient,
         subject: "Please verify your account".to_string(),
-        text: format!("Hi,\nThanks for signing up! Please confirm your email address by clicking on the link below.\n\n{}\n\nIf you did not sign up for an account, please disregard this email.", link),
-        html: format!("<!doctype html><html><head><title>Confirmation</title></head><body><p>Hi,<p>Thanks for signing up! Please confirm your email address by clicking on the link below.<p><a href=\"{}\">{}</a><p>If you did not sign up for an account, please disregard this email.</body></html>", link, link)
+        text: format!("Hi,\nThanks for signing up! Please confirm your email address by clicking on the link below. The link is valid for 24 hours.\n\n{}\n\nIf you did not sign up for an account, please disregard this email.", link),
+        html: format!("<!doctype html><html><head><title>Confirmation</title></head><body><p>Hi,<p>Thanks for signing up! Please confirm your email address by clicking on the link below. The link is valid for 24 hours.<p><a href=\"{}\">{}</a><p>If you did not sign up for an account, please disregard this email.</body></html>", link, link)
+    }
+}
+
+pub fn create_staff_invitation_email(invitation_url: String, mail_domain: String, recipient: String, invitation: String) -> Email {
+    let link: String = format!("{}/{}", invitation_url, invitation);
+    Email { 
+        from: format!("Admin <confirmation@{}>", mail_domain),
+        to: recipient,
+        subject: "You have been invited to join the team".to_string(),
+        text: format!("Hi,\nYou have been invited to join the team. Please choose a password to activate your account by clicking on the link below. The link is valid for 7 days.\n\n{}\n\nIf you were not expecting this invitation, please disregard this email.", link),
+        html: format!("<!doctype html><html><head><title>Invitation</title></head><body><p>Hi,<p>You have been invited to join the team. Please choose a password to activate your account by clicking on the link below. The link is valid for 7 days.<p><a href=\"{}\">{}</a><p>If you were not expecting this invitation, please disregard this email.</body></html>", link, link)
+    }
+}
+
+pub fn create_password_reset_email(reset_url: String, mail_domain: String, recipient: String, token: String) -> Email {
+    let link: String = format!("{}/{}", reset_url, token);
+    Email { 
+        from: format!("Admin <confirmation@{}>", mail_domain),
+        to: recipient,
+        subject: "Reset your password".to_string(),
+        text: format!("Hi,\nSomeone asked to reset the password for your account. You can choose a new password by clicking on the link below. The link is valid for 1 hour and can only be used once.\n\n{}\n\nIf you did not ask to reset your password, please disregard this email.", link),
+        html: format!("<!doctype html><html><head><title>Password reset</title></head><body><p>Hi,<p>Someone asked to reset the password for your account. You can choose a new password by clicking on the link below. The link is valid for 1 hour and can only be used once.<p><a href=\"{}\">{}</a><p>If you did not ask to reset your password, please disregard this email.</body></html>", link, link)
     }
 }
 
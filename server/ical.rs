@@ -0,0 +1,72 @@
+use chrono::{DateTime, Utc};
+use std::time::SystemTime;
+
+// RFC 5545 limits content lines to 75 octets, not counting the line break
+const MAX_LINE_OCTETS: usize = 75;
+
+pub struct Event {
+    pub uid: String,
+    pub start: SystemTime,
+    pub summary: String,
+    pub description: Option<String>,
+    pub url: Option<String>,
+}
+
+// a VCALENDAR with one VEVENT per event, every event is a point in time with no duration
+pub fn calendar(name: &str, events: &[Event]) -> String {
+    let stamp = date_time(SystemTime::now());
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//dokku-test//editorial calendar//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        format!("X-WR-CALNAME:{}", escape(name)),
+    ];
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", escape(&event.uid)));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.push(format!("DTSTART:{}", date_time(event.start)));
+        lines.push(format!("SUMMARY:{}", escape(&event.summary)));
+        if let Some(description) = &event.description {
+            lines.push(format!("DESCRIPTION:{}", escape(description)));
+        }
+        if let Some(url) = &event.url {
+            lines.push(format!("URL:{}", url));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter()
+        .map(|line| fold(line) + "\r\n")
+        .collect()
+}
+
+fn date_time(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace(['\n', '\r'], "\\n")
+}
+
+// continuation lines start with a space, which counts towards their length,
+// and a multi-byte character (e.g. Chinese) is never split across lines
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            octets = 1;
+        }
+        folded.push(c);
+        octets += c.len_utf8();
+    }
+    folded
+}
@@ -1,36 +1,196 @@
-use actix_identity::Identity;
+use std::collections::HashMap;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use actix_web::{web, dev::Payload, http::header, Error, FromRequest, HttpRequest, HttpResponse};
+use actix_web::error::InternalError;
+use actix_identity::RequestIdentity;
 
 use crate::database;
 
-pub fn to_credentials(id: Identity) -> Option<database::Credentials> {
-	id.identity()
-		.and_then(|identity| {
-			match serde_json::from_str(&identity) {
-				Ok(credentials) => Some(credentials),
-				Err(_) => None
+// how long a session looked up for a cookie is trusted before asking the database again
+const SESSION_CACHE_TTL: Duration = Duration::from_secs(30);
+
+// stale entries are only cleared out once there are this many
+const SESSION_CACHE_SIZE: usize = 1000;
+
+// session token -> when it was checked, and who it belongs to (None if the cookie may no longer log in)
+type SessionCache = HashMap<String, (Instant, Option<database::Credentials>)>;
+
+lazy_static! {
+	static ref SESSION_CACHE: Mutex<SessionCache> = Mutex::new(HashMap::new());
+	// comma separated ips of the proxies in front of the server, e.g. TRUSTED_PROXIES=172.17.0.1 for dokku's nginx
+	static ref TRUSTED_PROXIES: Vec<IpAddr> = std::env::var("TRUSTED_PROXIES")
+		.map(|v| v.split(',').filter_map(|ip| ip.trim().parse().ok()).collect())
+		.unwrap_or_default();
+}
+
+// the ip without the port, which changes with every connection
+// clients can send any forwarding headers they like, so X-Forwarded-For is only read when the
+// connection comes from a trusted proxy, and then only the last entry, which the proxy added itself
+pub fn client_ip(req: &HttpRequest) -> String {
+	let peer = match req.peer_addr() {
+		Some(addr) => addr.ip(),
+		None => return "unknown".to_string()
+	};
+	if !TRUSTED_PROXIES.contains(&peer) {
+		return peer.to_string();
+	}
+	req.headers().get("x-forwarded-for")
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.rsplit(',').next())
+		.and_then(|ip| ip.trim().parse::<IpAddr>().ok())
+		.unwrap_or(peer)
+		.to_string()
+}
+
+pub fn user_agent(req: &HttpRequest) -> Option<String> {
+	req.headers().get(header::USER_AGENT)
+		.and_then(|value| value.to_str().ok())
+		.map(|value| value.to_string())
+}
+
+// the cookie only holds a session token, who it belongs to, their roles and whether they are still
+// active come from the database so that revoking a session or role does not wait for the cookie to expire
+pub async fn current_credentials(req: &HttpRequest) -> Option<database::Credentials> {
+	let token = req.get_identity()?;
+
+	let cached = SESSION_CACHE.lock().ok()?
+		.get(&token)
+		.filter(|(checked, _)| checked.elapsed() < SESSION_CACHE_TTL)
+		.map(|(_, credentials)| credentials.clone());
+
+	match cached {
+		Some(credentials) => credentials,
+		None => {
+			let db = req.app_data::<web::Data<database::DB>>()?.clone();
+			// fail closed: a user is not trusted if their session could not be checked
+			let credentials = database::session_credentials(db, token.clone(), client_ip(req), user_agent(req)).await.ok()?;
+			let mut cache = SESSION_CACHE.lock().ok()?;
+			if cache.len() >= SESSION_CACHE_SIZE {
+				cache.retain(|_, (checked, _)| checked.elapsed() < SESSION_CACHE_TTL);
+			}
+			cache.insert(token, (Instant::now(), credentials.clone()));
+			credentials
+		}
+	}
+}
+
+// call after changing anyone's roles, activation, password or sessions, so this server sees it straight away
+pub fn forget_cached_sessions() {
+	if let Ok(mut cache) = SESSION_CACHE.lock() {
+		cache.clear();
+	}
+}
+
+// a role a handler can require, Admin always satisfies it
+pub trait RoleSpec {
+	const ROLE: database::Role;
+}
+
+pub struct Admin;
+pub struct Author;
+// no handler needs only Reviewer yet, reviewing goes through the workflow checks
+#[allow(dead_code)]
+pub struct Reviewer;
+pub struct Publisher;
+
+impl RoleSpec for Admin {
+	const ROLE: database::Role = database::Role::Admin;
+}
+
+impl RoleSpec for Author {
+	const ROLE: database::Role = database::Role::Author;
+}
+
+impl RoleSpec for Reviewer {
+	const ROLE: database::Role = database::Role::Reviewer;
+}
+
+impl RoleSpec for Publisher {
+	const ROLE: database::Role = database::Role::Publisher;
+}
+
+pub fn has_role(credentials: &database::Credentials, role: database::Role) -> bool {
+	credentials.roles.contains(&(role as i32)) || credentials.roles.contains(&(database::Role::Admin as i32))
+}
+
+fn unauthorized() -> Error {
+	InternalError::from_response("", HttpResponse::Unauthorized().finish()).into()
+}
+
+fn forbidden() -> Error {
+	InternalError::from_response("", HttpResponse::Forbidden().finish()).into()
+}
+
+// extractor for setting up two-factor authentication, which users whose roles require it
+// have to be able to do before anything else
+pub struct RequireSession {
+	pub credentials: database::Credentials,
+}
+
+impl FromRequest for RequireSession {
+	type Config = ();
+	type Error = Error;
+	type Future = Pin<Box<dyn Future<Output = Result<Self, Error>>>>;
+
+	fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+		let req = req.clone();
+		Box::pin(async move {
+			match current_credentials(&req).await {
+				Some(credentials) => Ok(RequireSession { credentials }),
+				None => Err(unauthorized())
 			}
 		})
+	}
+}
+
+// extractor for handlers that need a logged in, active user with any roles
+pub struct RequireLogin {
+	pub credentials: database::Credentials,
 }
 
-pub fn get_username(id: Identity) -> Option<String> {
-	to_credentials(id)
-		.and_then(|credentials| Some(credentials.username))
+impl FromRequest for RequireLogin {
+	type Config = ();
+	type Error = Error;
+	type Future = Pin<Box<dyn Future<Output = Result<Self, Error>>>>;
+
+	fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+		let req = req.clone();
+		Box::pin(async move {
+			match current_credentials(&req).await {
+				Some(credentials) if credentials.needs_two_factor => Err(forbidden()),
+				Some(credentials) => Ok(RequireLogin { credentials }),
+				None => Err(unauthorized())
+			}
+		})
+	}
 }
 
-pub fn get_roles(id: Identity) -> Option<Vec<i32>> {
-	to_credentials(id)
-		.and_then(|credentials| Some(credentials.roles))
+// extractor for handlers that need a role, e.g. `user: RequireRole<Author>`
+// anonymous users get 401, users without the role or still without required two-factor authentication get 403
+pub struct RequireRole<R: RoleSpec> {
+	pub credentials: database::Credentials,
+	role: PhantomData<R>,
 }
 
-pub fn can_write_article(id: Identity) -> bool {
-	match get_roles(id) {
-		// Author before Admin as Author is likely more common
-		Some(roles) => {
-			if roles.contains(&2) || roles.contains(&1)  {
-				true
-			} else {
-				false
-			}},
-		None => false
+impl<R: RoleSpec + 'static> FromRequest for RequireRole<R> {
+	type Config = ();
+	type Error = Error;
+	type Future = Pin<Box<dyn Future<Output = Result<Self, Error>>>>;
+
+	fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+		let req = req.clone();
+		Box::pin(async move {
+			match current_credentials(&req).await {
+				Some(credentials) if credentials.needs_two_factor => Err(forbidden()),
+				Some(credentials) if has_role(&credentials, R::ROLE) => Ok(RequireRole { credentials, role: PhantomData }),
+				Some(_) => Err(forbidden()),
+				None => Err(unauthorized())
+			}
+		})
 	}
-}
\ No newline at end of file
+}
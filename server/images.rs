@@ -0,0 +1,135 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use sha2::{Sha256, Digest};
+use uuid::Uuid;
+use image::{DynamicImage, ImageFormat};
+use image::imageops::FilterType;
+
+// the placeholder ships with the site, uploads go to UPLOAD_DIR so they can live on a persistent volume
+pub const PLACEHOLDER: &str = "static/images/placeholder.jpg";
+pub const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+
+lazy_static! {
+    pub static ref IMAGE_DIR: String = std::env::var("UPLOAD_DIR").unwrap_or_else(|_| "static/images".to_string());
+    // kept next to the uploads, so it's writable wherever they are
+    pub static ref CACHE_DIR: String = format!("{}/cache", *IMAGE_DIR);
+}
+
+// only these derivatives are ever generated, so the cache stays bounded
+pub const WIDTHS: [u32; 5] = [320, 640, 960, 1280, 1920];
+pub const FORMATS: [&str; 3] = ["jpg", "png", "webp"];
+
+// file extension for the image type, decided by magic bytes rather than what the client claims
+pub fn sniff(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("jpg")
+    } else if bytes.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        Some("png")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("gif")
+    } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("webp")
+    } else {
+        None
+    }
+}
+
+// contents of the first file field in a multipart/form-data body
+pub fn multipart_file<'a>(content_type: &str, body: &'a [u8]) -> Option<&'a [u8]> {
+    let boundary = content_type
+        .split(';')
+        .map(|param| param.trim())
+        .find(|param| param.starts_with("boundary="))
+        .map(|param| param["boundary=".len()..].trim_matches('"'))?;
+    let delimiter = format!("--{}", boundary);
+
+    let mut rest = &body[find(body, delimiter.as_bytes())? + delimiter.len()..];
+    loop {
+        // "--" after a delimiter marks the end of the body
+        if rest.starts_with(b"--") {
+            return None;
+        }
+        let headers_end = find(rest, b"\r\n\r\n")?;
+        let headers = String::from_utf8_lossy(&rest[..headers_end]).to_lowercase();
+        let content = &rest[headers_end + 4..];
+        let content_end = find(content, format!("\r\n{}", delimiter).as_bytes())?;
+
+        if headers.contains("filename=") {
+            return Some(&content[..content_end]);
+        }
+        rest = &content[content_end + 2 + delimiter.len()..];
+    }
+}
+
+// stores the image under the hash of its contents, returns the filename
+pub fn store(bytes: &[u8], extension: &str) -> io::Result<String> {
+    let filename = format!("{:x}.{}", Sha256::digest(bytes), extension);
+    let path = Path::new(&*IMAGE_DIR).join(&filename);
+
+    // same hash means the same image is already stored
+    if !path.exists() {
+        // write then rename so a half-written file is never served
+        let temp = Path::new(&*IMAGE_DIR).join(format!("{}.{}.tmp", filename, Uuid::new_v4().to_simple()));
+        fs::create_dir_all(&*IMAGE_DIR)?;
+        fs::write(&temp, bytes)?;
+        fs::rename(&temp, &path)?;
+    }
+    Ok(filename)
+}
+
+pub fn allowed_variant(width: Option<u32>, format: Option<&str>) -> bool {
+    width.is_none_or(|w| WIDTHS.contains(&w)) && format.is_none_or(|f| FORMATS.contains(&f))
+}
+
+// path to a resized and/or re-encoded copy of an image, generated on first request
+pub fn variant(filename: &str, width: Option<u32>, format: Option<&str>) -> io::Result<PathBuf> {
+    if filename.contains('/') || filename.starts_with('.') || !allowed_variant(width, format) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "image variant not allowed"));
+    }
+
+    let original = Path::new(&*IMAGE_DIR).join(filename);
+    let extension = format
+        .or_else(|| original.extension().and_then(|e| e.to_str()))
+        .unwrap_or("jpg");
+    let size = width.map_or("full".to_string(), |w| format!("{}w", w));
+    let cached = Path::new(&*CACHE_DIR).join(format!("{}.{}.{}", filename, size, extension));
+
+    // originals are content-addressed, but older uploads may still be replaced in place
+    let modified = fs::metadata(&original)?.modified()?;
+    if let Ok(cache_modified) = fs::metadata(&cached).and_then(|m| m.modified()) {
+        if cache_modified >= modified {
+            return Ok(cached);
+        }
+    }
+
+    // InvalidData tells the caller the original just can't be converted, e.g. a GIF
+    let mut img = image::open(&original)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    if let Some(w) = width {
+        // never upscale
+        if w < img.width() {
+            img = img.resize(w, u32::MAX, FilterType::Lanczos3);
+        }
+    }
+
+    let (img, format) = match extension {
+        "png" => (img, ImageFormat::Png),
+        "webp" => (DynamicImage::ImageRgba8(img.to_rgba8()), ImageFormat::WebP),
+        _ => (DynamicImage::ImageRgb8(img.to_rgb8()), ImageFormat::Jpeg),
+    };
+
+    let temp = Path::new(&*CACHE_DIR).join(format!("{}.tmp", Uuid::new_v4().to_simple()));
+    fs::create_dir_all(&*CACHE_DIR)?;
+    img.save_with_format(&temp, format).map_err(to_io_error)?;
+    fs::rename(&temp, &cached)?;
+    Ok(cached)
+}
+
+fn to_io_error(e: image::ImageError) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
@@ -1,20 +1,33 @@
-use actix_web::{web, App, HttpServer, HttpResponse, Responder, Result};
+use actix_web::{web, App, HttpServer, HttpRequest, HttpResponse, Responder, Result};
 use actix_web::middleware::Logger;
+use actix_web::error::{self, BlockingError};
+use actix_web::http::header;
 use actix_files as fs;
 use actix_identity::{Identity, CookieIdentityPolicy, IdentityService};
-use serde::{Serialize};
+use serde::{Serialize, Deserialize};
 use serde_json;
 use std::thread;
+use std::time::{Duration, SystemTime};
+use actix_rt::time;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
 use env_logger;
 
 #[macro_use]
 extern crate lazy_static;
 
+mod anchor;
 #[allow(dead_code)]
 mod database;
+mod diff;
 mod email;
 mod html;
+mod ical;
 mod identity;
+mod images;
+mod markdown;
+mod text;
+mod two_factor;
 
 // API
 
@@ -23,32 +36,153 @@ struct Msg {
     msg: String,
 }
 
-async fn hello(db: web::Data<database::DB>, id: Identity) -> impl Responder {
+// who sent a request, stored with log entries about logins and registrations
+fn request_detail(req: &HttpRequest) -> serde_json::Value {
+    let user_agent = req.headers().get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok());
+    // behind a trusted proxy the peer is the proxy, and ip is the client it forwarded for
+    serde_json::json!({
+        "ip": identity::client_ip(req),
+        "peer": req.peer_addr().map(|addr| addr.ip().to_string()),
+        "user_agent": user_agent,
+    })
+}
+
+fn db_error(e: BlockingError<database::DBError>) -> HttpResponse {
+    match e {
+        BlockingError::Error(database::DBError::NotFoundError(msg)) => HttpResponse::NotFound().json(Msg { msg }),
+        BlockingError::Error(database::DBError::ValidationError(msg)) => HttpResponse::BadRequest().json(Msg { msg }),
+        BlockingError::Error(database::DBError::AuthenticationError(msg)) => HttpResponse::Forbidden().json(Msg { msg }),
+        BlockingError::Error(database::DBError::ConflictError(msg)) => HttpResponse::Conflict().json(Msg { msg }),
+        BlockingError::Error(database::DBError::GoneError(msg)) => HttpResponse::Gone().json(Msg { msg }),
+        e => HttpResponse::InternalServerError().json(Msg { msg: e.to_string() })
+    }
+}
+
+// the identity is the session token, so only the username it belongs to is shown
+async fn hello(req: HttpRequest, db: web::Data<database::DB>) -> impl Responder {
+    let username = identity::current_credentials(&req).await.map(|credentials| credentials.username);
     match database::select_hello(db).await {
-        Ok(x) => web::Json(Msg { msg: format!("{}: {}", x, id.identity().unwrap_or_else(|| "idk".to_string())) }),
+        Ok(x) => web::Json(Msg { msg: format!("{}: {}", x, username.unwrap_or_else(|| "idk".to_string())) }),
         Err(e) => web::Json(Msg { msg: e.to_string() })
     }
 }
 
-async fn login(info: web::Json<database::Login>, id: Identity, db: web::Data<database::DB>) -> impl Responder {
+fn too_many_requests(wait: i32) -> HttpResponse {
+    HttpResponse::TooManyRequests()
+        .header(header::RETRY_AFTER, wait.to_string())
+        .json(Msg { msg: "Too many attempts, please try again later".to_string() })
+}
+
+// 429 if any of the (scope, key) pairs is backing off or locked out, otherwise the attempt is
+// counted straight away, so requests sent in parallel can't all get through before one fails
+// handlers refund the attempt if it turns out not to be a failure
+async fn check_throttle(req: &HttpRequest, db: web::Data<database::DB>, attempts: Vec<(&'static str, String)>) -> Option<HttpResponse> {
+    match database::throttle_attempt(db, attempts, request_detail(req)).await {
+        Ok(0) => None,
+        Ok(wait) => Some(too_many_requests(wait)),
+        Err(e) => Some(db_error(e))
+    }
+}
+
+async fn login(req: HttpRequest, info: web::Json<database::Login>, id: Identity, db: web::Data<database::DB>) -> impl Responder {
     
     let login_info = info.into_inner();
+    let ip = identity::client_ip(&req);
 
-    match database::authenticate(db, login_info.clone()).await {
+    if let Some(response) = check_throttle(&req, db.clone(), vec![("login_ip", ip.clone()), ("login_user", login_info.username.clone())]).await {
+        return response;
+    }
+
+    match database::authenticate(db.clone(), login_info.clone(), request_detail(&req)).await {
         Ok((credentials, msg)) => { 
-            // explicitly unwrap to null string if json fails, because this will show up in Elm as not logged in
-            id.remember(serde_json::to_string(&credentials).unwrap_or_else(|_| "null".to_string())); 
-            web::Json(Msg { msg: msg }) 
+            // the ip keeps its failures, or one good account would reset guessing at others
+            database::throttle_success(db.clone(), "login_user", login_info.username).await.ok();
+            database::throttle_refund(db.clone(), "login_ip", ip.clone()).await.ok();
+            match database::create_session(db, credentials.username, ip, identity::user_agent(&req)).await {
+                // the cookie is set either way, login_two_factor needs it to find the pending session
+                Ok((token, pending)) => {
+                    id.remember(token);
+                    if pending {
+                        HttpResponse::Ok().json(Msg { msg: "Two-factor code required".to_string() })
+                    } else {
+                        HttpResponse::Ok().json(Msg { msg: msg })
+                    }
+                },
+                Err(e) => db_error(e)
+            }
         },
-        Err(e) => web::Json(Msg { msg: e.to_string() })
+        Err(e) => {
+            // unknown usernames count too, so guessing them is just as slow
+            let failed = match &e {
+                BlockingError::Error(database::DBError::AuthenticationError(reason)) =>
+                    reason == "Wrong password" || reason.ends_with("does not exist"),
+                _ => false
+            };
+            if !failed {
+                database::throttle_refund(db.clone(), "login_ip", ip).await.ok();
+                database::throttle_refund(db, "login_user", login_info.username).await.ok();
+            }
+            HttpResponse::Ok().json(Msg { msg: e.to_string() })
+        }
     }
 }
 
-async fn register(info: web::Json<database::Register>, db: web::Data<database::DB>) -> impl Responder {
-    
+// wrong codes count towards the same limit as the second login step, other errors give the attempt back
+fn wrong_code(e: &BlockingError<database::DBError>) -> bool {
+    match e {
+        BlockingError::Error(database::DBError::AuthenticationError(reason)) => reason == "Wrong code",
+        _ => false
+    }
+}
+
+// second step of logging in, for users with two-factor authentication
+async fn login_two_factor(req: HttpRequest, info: web::Json<database::TwoFactorCode>, id: Identity, db: web::Data<database::DB>) -> impl Responder {
+    let no_login = || HttpResponse::NotFound().json(Msg { msg: "Login does not exist".to_string() });
+    let token = match id.identity() {
+        Some(token) => token,
+        None => return no_login()
+    };
+    let username = match database::pending_login(db.clone(), token.clone()).await {
+        Ok(Some(username)) => username,
+        Ok(None) => return no_login(),
+        Err(e) => return db_error(e)
+    };
+
+    if let Some(response) = check_throttle(&req, db.clone(), vec![("two_factor_user", username.clone())]).await {
+        return response;
+    }
+
+    match database::complete_login(db.clone(), token, info.into_inner().code, request_detail(&req)).await {
+        Ok(msg) => {
+            database::throttle_success(db, "two_factor_user", username).await.ok();
+            // the session may have been looked up while it was pending
+            identity::forget_cached_sessions();
+            HttpResponse::Ok().json(Msg { msg })
+        },
+        Err(e) => {
+            if !wrong_code(&e) {
+                database::throttle_refund(db, "two_factor_user", username).await.ok();
+            }
+            db_error(e)
+        }
+    }
+}
+
+async fn register(req: HttpRequest, info: web::Json<database::Register>, db: web::Data<database::DB>) -> impl Responder {
+    if !*OPEN_REGISTRATION {
+        return HttpResponse::Forbidden().json(Msg { msg: "Registration is by invitation only".to_string() });
+    }
+
+    // every registration sends an email, so all attempts count, not just failures
+    let ip = identity::client_ip(&req);
+    if let Some(response) = check_throttle(&req, db.clone(), vec![("register_ip", ip)]).await {
+        return response;
+    }
+
     let register_info = info.into_inner();
 
-    match database::register(db, register_info.clone()).await {
+    match database::register(db, register_info.clone(), request_detail(&req)).await {
         Ok(s) => {
             let mailer = email::create_mail_client(MAILGUN_KEY.to_string(), EMAIL_DOMAIN.to_string());
             let mail = email::create_email(
@@ -57,25 +191,501 @@ async fn register(info: web::Json<database::Register>, db: web::Data<database::D
                         register_info.username,
                         s);
             match email::send_verification_email(mailer, mail).await {
-                Ok(_) => web::Json(Msg { msg: "Verification email sent!".to_string() }),
-                Err(e) => web::Json(Msg { msg: e })
+                Ok(_) => HttpResponse::Ok().json(Msg { msg: "Verification email sent!".to_string() }),
+                Err(e) => HttpResponse::Ok().json(Msg { msg: e })
             }},
-        Err(e) => web::Json(Msg { msg: e.to_string() })
+        Err(e) => HttpResponse::Ok().json(Msg { msg: e.to_string() })
     }
 }
 
-async fn confirm(info: web::Path<String>, db: web::Data<database::DB>) -> impl Responder {
-    match database::confirm(db, info.into_inner()).await {
-        Ok(s) => web::Json(Msg { msg: s }),
-        Err(e) => web::Json(Msg { msg: e.to_string() })
+// opened from the email, so it lands on a page of the site instead of showing json
+// unknown tokens count as failures, so they can't be guessed at speed
+async fn confirm(req: HttpRequest, info: web::Path<String>, db: web::Data<database::DB>) -> impl Responder {
+    let ip = identity::client_ip(&req);
+    if let Some(response) = check_throttle(&req, db.clone(), vec![("confirm_ip", ip.clone())]).await {
+        return response;
+    }
+    let result = match database::confirm(db.clone(), info.into_inner()).await {
+        Ok(_) => "success",
+        Err(BlockingError::Error(database::DBError::GoneError(_))) => "expired",
+        Err(BlockingError::Error(database::DBError::NotFoundError(_))) => "invalid",
+        Err(e) => {
+            database::throttle_refund(db, "confirm_ip", ip).await.ok();
+            return db_error(e)
+        }
+    };
+    if result != "invalid" {
+        database::throttle_refund(db, "confirm_ip", ip).await.ok();
+    }
+    HttpResponse::SeeOther()
+        .header(header::LOCATION, format!("/confirmation/{}", result))
+        .finish()
+}
+
+// answers the same whether or not there is an account waiting for confirmation
+// every request may send an email, so all of them count
+async fn resend_confirmation(req: HttpRequest, db: web::Data<database::DB>, info: web::Json<database::ResendConfirmation>) -> impl Responder {
+    let username = info.into_inner().username.trim().to_string();
+    let ip = identity::client_ip(&req);
+    if let Some(response) = check_throttle(&req, db.clone(), vec![("resend_ip", ip), ("resend_user", username.clone())]).await {
+        return response;
     }
+    match database::resend_confirmation(db, username.clone(), request_detail(&req)).await {
+        Ok(Some(token)) => {
+            let mailer = email::create_mail_client(MAILGUN_KEY.to_string(), EMAIL_DOMAIN.to_string());
+            let mail = email::create_email(
+                        format!("{}/api/confirm", *SITE_DOMAIN),
+                        EMAIL_DOMAIN.to_string(),
+                        username,
+                        token);
+            actix_rt::spawn(async move {
+                if let Err(e) = email::send_verification_email(mailer, mail).await {
+                    log::warn!("Could not resend confirmation email: {}", e);
+                }
+            });
+        },
+        Ok(None) => (),
+        Err(e) => return db_error(e)
+    }
+    HttpResponse::Ok().json(Msg { msg: "If the account is waiting for confirmation, a new link has been sent".to_string() })
 }
 
-async fn logout(id: Identity) -> impl Responder {
+async fn invite_staff(db: web::Data<database::DB>, admin: identity::RequireRole<identity::Admin>, info: web::Json<database::NewStaffInvitation>) -> impl Responder {
+    let info = info.into_inner();
+    let email_address = info.email.trim().to_string();
+    match database::create_staff_invitation(db, admin.credentials.username, info).await {
+        Ok(token) => {
+            let mailer = email::create_mail_client(MAILGUN_KEY.to_string(), EMAIL_DOMAIN.to_string());
+            let mail = email::create_staff_invitation_email(
+                        format!("{}/invitation", *SITE_DOMAIN),
+                        EMAIL_DOMAIN.to_string(),
+                        email_address,
+                        token);
+            // the invitation is kept if sending fails, inviting again replaces it
+            match email::send_verification_email(mailer, mail).await {
+                Ok(_) => HttpResponse::Created().json(Msg { msg: "Invitation sent!".to_string() }),
+                Err(e) => HttpResponse::BadGateway().json(Msg { msg: e })
+            }},
+        Err(e) => db_error(e)
+    }
+}
+
+async fn staff_invitations(db: web::Data<database::DB>, admin: identity::RequireRole<identity::Admin>) -> impl Responder {
+    match database::get_staff_invitations(db, admin.credentials.username).await {
+        Ok(invitations) => HttpResponse::Ok().json(invitations),
+        Err(e) => db_error(e)
+    }
+}
+
+async fn revoke_staff_invitation(db: web::Data<database::DB>, admin: identity::RequireRole<identity::Admin>, invitation_id: web::Path<i32>) -> impl Responder {
+    match database::revoke_staff_invitation(db, admin.credentials.username, invitation_id.into_inner()).await {
+        Ok(s) => HttpResponse::Ok().json(Msg { msg: s }),
+        Err(e) => db_error(e)
+    }
+}
+
+// creates the account with the roles from the invitation, the username is the invited email
+async fn accept_staff_invitation(db: web::Data<database::DB>, token: web::Path<String>, info: web::Json<database::AcceptInvitation>) -> impl Responder {
+    match database::accept_staff_invitation(db, token.into_inner(), info.into_inner()).await {
+        Ok(s) => HttpResponse::Created().json(Msg { msg: s }),
+        Err(e) => db_error(e)
+    }
+}
+
+// answers the same whether or not the account exists, and sends the email in the background
+// so the response time doesn't give it away either
+// every request may send an email, so all of them count
+async fn forgot_password(req: HttpRequest, db: web::Data<database::DB>, info: web::Json<database::ForgotPassword>) -> impl Responder {
+    let username = info.into_inner().username.trim().to_string();
+    let ip = identity::client_ip(&req);
+    if let Some(response) = check_throttle(&req, db.clone(), vec![("reset_ip", ip), ("reset_user", username.clone())]).await {
+        return response;
+    }
+    match database::request_password_reset(db, username.clone(), request_detail(&req)).await {
+        Ok(Some(token)) => {
+            let mailer = email::create_mail_client(MAILGUN_KEY.to_string(), EMAIL_DOMAIN.to_string());
+            let mail = email::create_password_reset_email(
+                        format!("{}/reset_password", *SITE_DOMAIN),
+                        EMAIL_DOMAIN.to_string(),
+                        username,
+                        token);
+            actix_rt::spawn(async move {
+                if let Err(e) = email::send_verification_email(mailer, mail).await {
+                    log::warn!("Could not send password reset email: {}", e);
+                }
+            });
+        },
+        Ok(None) => (),
+        Err(e) => return db_error(e)
+    }
+    HttpResponse::Ok().json(Msg { msg: "If the account exists, a reset link has been sent".to_string() })
+}
+
+// the new password logs out every existing session of the account
+async fn reset_password(req: HttpRequest, db: web::Data<database::DB>, token: web::Path<String>, info: web::Json<database::PasswordReset>) -> impl Responder {
+    match database::reset_password(db, token.into_inner(), info.into_inner(), request_detail(&req)).await {
+        Ok(s) => {
+            identity::forget_cached_sessions();
+            HttpResponse::Ok().json(Msg { msg: s })
+        },
+        Err(e) => db_error(e)
+    }
+}
+
+// ends the session on the server too, so a copy of the cookie stops working
+async fn logout(db: web::Data<database::DB>, id: Identity) -> impl Responder {
+    if let Some(token) = id.identity() {
+        if let Err(e) = database::end_session(db, token).await {
+            return db_error(e);
+        }
+        identity::forget_cached_sessions();
+    }
     id.forget();
     HttpResponse::Ok().finish()
 }
 
+async fn sessions(db: web::Data<database::DB>, user: identity::RequireSession, id: Identity) -> impl Responder {
+    match database::get_sessions(db, user.credentials.username, id.identity()).await {
+        Ok(sessions) => HttpResponse::Ok().json(sessions),
+        Err(e) => db_error(e)
+    }
+}
+
+async fn revoke_session(db: web::Data<database::DB>, user: identity::RequireSession, session_id: web::Path<i32>) -> impl Responder {
+    match database::revoke_session(db, user.credentials.username, session_id.into_inner()).await {
+        Ok(s) => {
+            identity::forget_cached_sessions();
+            HttpResponse::Ok().json(Msg { msg: s })
+        },
+        Err(e) => db_error(e)
+    }
+}
+
+#[derive(Deserialize)]
+struct RevokeSessions {
+    // stay logged in here and only end the other sessions
+    others: Option<bool>,
+}
+
+// log out everywhere
+async fn revoke_sessions(db: web::Data<database::DB>, user: identity::RequireSession, id: Identity, query: web::Query<RevokeSessions>) -> impl Responder {
+    let keep_token = if query.others.unwrap_or(false) { id.identity() } else { None };
+    let logout = keep_token.is_none();
+    match database::revoke_sessions(db, user.credentials.username, keep_token).await {
+        Ok(s) => {
+            identity::forget_cached_sessions();
+            if logout {
+                id.forget();
+            }
+            HttpResponse::Ok().json(Msg { msg: s })
+        },
+        Err(e) => db_error(e)
+    }
+}
+
+#[derive(Serialize)]
+struct TwoFactorSetup {
+    secret: String,
+    uri: String,
+    // svg image of the uri for the authenticator app to scan
+    qr: Option<String>,
+}
+
+async fn two_factor_status(db: web::Data<database::DB>, user: identity::RequireSession) -> impl Responder {
+    match database::get_two_factor(db, user.credentials.username).await {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(e) => db_error(e)
+    }
+}
+
+async fn start_two_factor(db: web::Data<database::DB>, user: identity::RequireSession) -> impl Responder {
+    let username = user.credentials.username;
+    match database::start_two_factor(db, username.clone()).await {
+        Ok(secret) => {
+            let issuer = SITE_DOMAIN.trim_start_matches("https://").trim_start_matches("http://");
+            let uri = two_factor::provisioning_uri(issuer, &username, &secret);
+            HttpResponse::Ok().json(TwoFactorSetup { qr: two_factor::qr_svg(&uri), uri, secret })
+        },
+        Err(e) => db_error(e)
+    }
+}
+
+// turns it on and returns the recovery codes, other sessions are logged out since they only used a password
+async fn confirm_two_factor(req: HttpRequest, db: web::Data<database::DB>, user: identity::RequireSession, id: Identity, info: web::Json<database::TwoFactorCode>) -> impl Responder {
+    let username = user.credentials.username;
+    if let Some(response) = check_throttle(&req, db.clone(), vec![("two_factor_user", username.clone())]).await {
+        return response;
+    }
+    match database::confirm_two_factor(db.clone(), username.clone(), info.into_inner().code, id.identity()).await {
+        Ok(codes) => {
+            database::throttle_refund(db, "two_factor_user", username).await.ok();
+            identity::forget_cached_sessions();
+            HttpResponse::Ok().json(codes)
+        },
+        Err(e) => {
+            if !wrong_code(&e) {
+                database::throttle_refund(db, "two_factor_user", username).await.ok();
+            }
+            db_error(e)
+        }
+    }
+}
+
+async fn reset_recovery_codes(req: HttpRequest, db: web::Data<database::DB>, user: identity::RequireSession, info: web::Json<database::TwoFactorCode>) -> impl Responder {
+    let username = user.credentials.username;
+    if let Some(response) = check_throttle(&req, db.clone(), vec![("two_factor_user", username.clone())]).await {
+        return response;
+    }
+    match database::reset_recovery_codes(db.clone(), username.clone(), info.into_inner().code).await {
+        Ok(codes) => {
+            database::throttle_refund(db, "two_factor_user", username).await.ok();
+            HttpResponse::Ok().json(codes)
+        },
+        Err(e) => {
+            if !wrong_code(&e) {
+                database::throttle_refund(db, "two_factor_user", username).await.ok();
+            }
+            db_error(e)
+        }
+    }
+}
+
+async fn disable_two_factor(req: HttpRequest, db: web::Data<database::DB>, user: identity::RequireSession, info: web::Json<database::TwoFactorCode>) -> impl Responder {
+    let username = user.credentials.username;
+    if let Some(response) = check_throttle(&req, db.clone(), vec![("two_factor_user", username.clone())]).await {
+        return response;
+    }
+    match database::disable_two_factor(db.clone(), username.clone(), info.into_inner().code).await {
+        Ok(s) => {
+            database::throttle_refund(db, "two_factor_user", username).await.ok();
+            HttpResponse::Ok().json(Msg { msg: s })
+        },
+        Err(e) => {
+            if !wrong_code(&e) {
+                database::throttle_refund(db, "two_factor_user", username).await.ok();
+            }
+            db_error(e)
+        }
+    }
+}
+
+// from then on everyone with the role has to set up two-factor authentication before doing anything else
+async fn require_two_factor(db: web::Data<database::DB>, admin: identity::RequireRole<identity::Admin>, role: web::Path<i32>) -> impl Responder {
+    match database::set_role_two_factor(db, admin.credentials.username, role.into_inner(), true).await {
+        Ok(s) => {
+            identity::forget_cached_sessions();
+            HttpResponse::Ok().json(Msg { msg: s })
+        },
+        Err(e) => db_error(e)
+    }
+}
+
+async fn unrequire_two_factor(db: web::Data<database::DB>, admin: identity::RequireRole<identity::Admin>, role: web::Path<i32>) -> impl Responder {
+    match database::set_role_two_factor(db, admin.credentials.username, role.into_inner(), false).await {
+        Ok(s) => {
+            identity::forget_cached_sessions();
+            HttpResponse::Ok().json(Msg { msg: s })
+        },
+        Err(e) => db_error(e)
+    }
+}
+
+// current backoffs and lockouts
+async fn lockouts(db: web::Data<database::DB>, admin: identity::RequireRole<identity::Admin>) -> impl Responder {
+    match database::get_throttles(db, admin.credentials.username).await {
+        Ok(throttles) => HttpResponse::Ok().json(throttles),
+        Err(e) => db_error(e)
+    }
+}
+
+async fn clear_lockout(db: web::Data<database::DB>, admin: identity::RequireRole<identity::Admin>, path: web::Path<(String, String)>) -> impl Responder {
+    let (scope, key) = path.into_inner();
+    match database::clear_throttle(db, admin.credentials.username, scope, key).await {
+        Ok(s) => HttpResponse::Ok().json(Msg { msg: s }),
+        Err(e) => db_error(e)
+    }
+}
+
+#[derive(Deserialize)]
+struct UserSearch {
+    search: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DisplayName {
+    display_name: Option<String>,
+}
+
+// matches usernames and display names, lists everyone without a search
+async fn users(db: web::Data<database::DB>, admin: identity::RequireRole<identity::Admin>, query: web::Query<UserSearch>) -> impl Responder {
+    let search = query.into_inner().search.unwrap_or_default();
+    match database::get_users(db, admin.credentials.username, search.trim().to_string()).await {
+        Ok(users) => HttpResponse::Ok().json(users),
+        Err(e) => db_error(e)
+    }
+}
+
+async fn user(db: web::Data<database::DB>, admin: identity::RequireRole<identity::Admin>, user_id: web::Path<i32>) -> impl Responder {
+    match database::get_user(db, admin.credentials.username, user_id.into_inner()).await {
+        Ok(user) => HttpResponse::Ok().json(user),
+        Err(e) => db_error(e)
+    }
+}
+
+async fn grant_role(db: web::Data<database::DB>, admin: identity::RequireRole<identity::Admin>, path: web::Path<(i32, i32)>) -> impl Responder {
+    let (user_id, role) = path.into_inner();
+    match database::set_user_role(db, admin.credentials.username, user_id, role, true).await {
+        Ok(s) => {
+            identity::forget_cached_sessions();
+            HttpResponse::Ok().json(Msg { msg: s })
+        },
+        Err(e) => db_error(e)
+    }
+}
+
+async fn revoke_role(db: web::Data<database::DB>, admin: identity::RequireRole<identity::Admin>, path: web::Path<(i32, i32)>) -> impl Responder {
+    let (user_id, role) = path.into_inner();
+    match database::set_user_role(db, admin.credentials.username, user_id, role, false).await {
+        Ok(s) => {
+            identity::forget_cached_sessions();
+            HttpResponse::Ok().json(Msg { msg: s })
+        },
+        Err(e) => db_error(e)
+    }
+}
+
+// an empty or missing display name shows the username instead
+async fn set_display_name(db: web::Data<database::DB>, admin: identity::RequireRole<identity::Admin>, user_id: web::Path<i32>, info: web::Json<DisplayName>) -> impl Responder {
+    match database::set_display_name(db, admin.credentials.username, user_id.into_inner(), info.into_inner().display_name).await {
+        Ok(s) => HttpResponse::Ok().json(Msg { msg: s }),
+        Err(e) => db_error(e)
+    }
+}
+
+async fn deactivate_user(db: web::Data<database::DB>, admin: identity::RequireRole<identity::Admin>, user_id: web::Path<i32>) -> impl Responder {
+    match database::set_user_active(db, admin.credentials.username, user_id.into_inner(), false).await {
+        Ok(s) => {
+            identity::forget_cached_sessions();
+            HttpResponse::Ok().json(Msg { msg: s })
+        },
+        Err(e) => db_error(e)
+    }
+}
+
+async fn activate_user(db: web::Data<database::DB>, admin: identity::RequireRole<identity::Admin>, user_id: web::Path<i32>) -> impl Responder {
+    match database::set_user_active(db, admin.credentials.username, user_id.into_inner(), true).await {
+        Ok(s) => {
+            identity::forget_cached_sessions();
+            HttpResponse::Ok().json(Msg { msg: s })
+        },
+        Err(e) => db_error(e)
+    }
+}
+
+#[derive(Deserialize)]
+struct LogQuery {
+    subject: Option<String>,
+    user: Option<String>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    q: Option<String>,
+    after: Option<i32>,
+    limit: Option<i32>,
+    format: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LogPage {
+    entries: Vec<database::LogEntry>,
+    // pass as `after` for the next page, None on the last page
+    next: Option<i32>,
+}
+
+const LOG_PAGE_SIZE: i32 = 100;
+const MAX_LOG_PAGE_SIZE: i32 = 1000;
+const MAX_LOG_EXPORT: i32 = 10_000;
+
+// format is json (default, paged), csv or ndjson (exports, also keyset paged via the X-Next-After header)
+async fn logs(db: web::Data<database::DB>, admin: identity::RequireRole<identity::Admin>, query: web::Query<LogQuery>) -> impl Responder {
+    let query = query.into_inner();
+    let format = query.format.unwrap_or_else(|| "json".to_string());
+    let max = match format.as_str() {
+        "json" => MAX_LOG_PAGE_SIZE,
+        "csv" | "ndjson" => MAX_LOG_EXPORT,
+        _ => return HttpResponse::BadRequest().json(Msg { msg: "Format must be json, csv or ndjson".to_string() })
+    };
+    let limit = query.limit.unwrap_or(if format == "json" { LOG_PAGE_SIZE } else { MAX_LOG_EXPORT }).clamp(1, max);
+    let filter = database::LogFilter {
+        subject: query.subject.filter(|s| !s.is_empty()),
+        user: query.user.filter(|s| !s.is_empty()),
+        since: query.since.map(SystemTime::from),
+        until: query.until.map(SystemTime::from),
+        search: query.q.filter(|s| !s.trim().is_empty()),
+        after: query.after,
+    };
+
+    let entries = match database::get_logs(db, admin.credentials.username, filter, limit).await {
+        Ok(entries) => entries,
+        Err(e) => return db_error(e)
+    };
+    // a full page means there may be more
+    let next = if entries.len() as i32 == limit { entries.last().map(|e| e.id) } else { None };
+
+    let mut response = HttpResponse::Ok();
+    if let Some(next) = next {
+        response.header("X-Next-After", next.to_string());
+    }
+    match format.as_str() {
+        "csv" => response
+            .content_type("text/csv; charset=utf-8")
+            .header(header::CONTENT_DISPOSITION, "attachment; filename=\"logs.csv\"")
+            .body(logs_csv(&entries)),
+        "ndjson" => response
+            .content_type("application/x-ndjson")
+            .header(header::CONTENT_DISPOSITION, "attachment; filename=\"logs.ndjson\"")
+            .body(entries.iter()
+                .filter_map(|e| serde_json::to_string(&log_export_row(e)).ok())
+                .map(|line| line + "\n")
+                .collect::<String>()),
+        _ => response.json(LogPage { entries, next })
+    }
+}
+
+// exports use readable utc dates instead of the api's epoch timestamps
+fn log_export_row(e: &database::LogEntry) -> serde_json::Value {
+    serde_json::json!({
+        "id": e.id,
+        "subject": e.subject,
+        "user_id": e.user_id,
+        "username": e.username,
+        "date_created": DateTime::<Utc>::from(e.date_created).to_rfc3339(),
+        "entry": e.entry,
+        "detail": e.detail,
+    })
+}
+
+fn logs_csv(entries: &[database::LogEntry]) -> String {
+    // spreadsheets run cells starting with these as formulas, so they are quoted with '
+    fn field(s: &str) -> String {
+        let prefix = if s.starts_with(['=', '+', '-', '@', '\t', '\r']) { "'" } else { "" };
+        format!("\"{}{}\"", prefix, s.replace('"', "\"\""))
+    }
+    let mut csv = "id,subject,user_id,username,date_created,entry,detail\r\n".to_string();
+    for e in entries {
+        csv.push_str(&[
+            e.id.to_string(),
+            field(&e.subject),
+            e.user_id.map(|id| id.to_string()).unwrap_or_default(),
+            field(e.username.as_deref().unwrap_or("")),
+            DateTime::<Utc>::from(e.date_created).to_rfc3339(),
+            field(e.entry.as_deref().unwrap_or("")),
+            field(&e.detail.as_ref().map(|d| d.to_string()).unwrap_or_default()),
+        ].join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
 async fn articles(db: web::Data<database::DB>) -> impl Responder {
     match database::get_articles(db).await {
         Ok(article_list) => web::Json(article_list),
@@ -85,30 +695,508 @@ async fn articles(db: web::Data<database::DB>) -> impl Responder {
 
 async fn article(db: web::Data<database::DB>, id: web::Path<i32>) -> impl Responder {
     match database::get_article(db, id.into_inner()).await {
-        Ok(article) => web::Json(Some(article)),
-        Err(_) => web::Json(None)
+        Ok(article) => HttpResponse::Ok().json(article),
+        Err(e) => db_error(e)
+    }
+}
+
+#[derive(Deserialize)]
+struct TakeDown {
+    reason: Option<String>,
+}
+
+async fn disable_article(db: web::Data<database::DB>, publisher: identity::RequireRole<identity::Publisher>, article_id: web::Path<i32>, info: web::Json<TakeDown>) -> impl Responder {
+    let reason = info.into_inner().reason.filter(|r| !r.trim().is_empty());
+    if reason.is_none() {
+        return HttpResponse::BadRequest().json(Msg { msg: "A reason is required".to_string() });
+    }
+    match database::set_article_disabled(db, publisher.credentials.username, article_id.into_inner(), true, reason).await {
+        Ok(s) => HttpResponse::Ok().json(Msg { msg: s }),
+        Err(e) => db_error(e)
+    }
+}
+
+#[derive(Deserialize)]
+struct Schedule {
+    date_published: SystemTime,
+}
+
+async fn schedule_article(db: web::Data<database::DB>, publisher: identity::RequireRole<identity::Publisher>, article_id: web::Path<i32>, info: web::Json<Schedule>) -> impl Responder {
+    match database::schedule_article(db, publisher.credentials.username, article_id.into_inner(), info.date_published).await {
+        Ok(s) => HttpResponse::Ok().json(Msg { msg: s }),
+        Err(e) => db_error(e)
+    }
+}
+
+async fn enable_article(db: web::Data<database::DB>, publisher: identity::RequireRole<identity::Publisher>, article_id: web::Path<i32>, info: web::Json<TakeDown>) -> impl Responder {
+    match database::set_article_disabled(db, publisher.credentials.username, article_id.into_inner(), false, info.into_inner().reason).await {
+        Ok(s) => HttpResponse::Ok().json(Msg { msg: s }),
+        Err(e) => db_error(e)
+    }
+}
+
+// replaces all bylines, in display order
+async fn set_article_bylines(db: web::Data<database::DB>, author: identity::RequireRole<identity::Author>, article_id: web::Path<i32>, info: web::Json<Vec<database::BylineInput>>) -> impl Responder {
+    match database::set_article_bylines(db, author.credentials.username, article_id.into_inner(), info.into_inner()).await {
+        Ok(s) => HttpResponse::Ok().json(Msg { msg: s }),
+        Err(e) => db_error(e)
+    }
+}
+
+async fn guest_authors(db: web::Data<database::DB>, author: identity::RequireRole<identity::Author>) -> impl Responder {
+    match database::get_guest_authors(db, author.credentials.username).await {
+        Ok(guests) => HttpResponse::Ok().json(guests),
+        Err(e) => db_error(e)
+    }
+}
+
+async fn new_guest_author(db: web::Data<database::DB>, author: identity::RequireRole<identity::Author>, info: web::Json<database::GuestAuthor>) -> impl Responder {
+    let info = database::GuestAuthor { id: None, ..info.into_inner() };
+    match database::save_guest_author(db, author.credentials.username, info).await {
+        Ok(guest_id) => HttpResponse::Created().json(guest_id),
+        Err(e) => db_error(e)
+    }
+}
+
+async fn update_guest_author(db: web::Data<database::DB>, author: identity::RequireRole<identity::Author>, guest_id: web::Path<i32>, info: web::Json<database::GuestAuthor>) -> impl Responder {
+    let info = database::GuestAuthor { id: Some(guest_id.into_inner()), ..info.into_inner() };
+    match database::save_guest_author(db, author.credentials.username, info).await {
+        Ok(guest_id) => HttpResponse::Ok().json(guest_id),
+        Err(e) => db_error(e)
+    }
+}
+
+#[derive(Deserialize)]
+struct AuthorQuery {
+    after: Option<i32>,
+    limit: Option<i32>,
+}
+
+#[derive(Serialize)]
+struct AuthorPage {
+    #[serde(flatten)]
+    profile: database::AuthorProfile,
+    articles: Vec<database::ArticleSummary>,
+    // pass as `after` for the next page, None on the last page
+    next: Option<i32>,
+}
+
+const AUTHOR_PAGE_SIZE: i32 = 20;
+const MAX_AUTHOR_PAGE_SIZE: i32 = 100;
+
+async fn author_profile(db: web::Data<database::DB>, author: web::Path<String>, query: web::Query<AuthorQuery>) -> impl Responder {
+    let author = author.into_inner();
+    let limit = query.limit.unwrap_or(AUTHOR_PAGE_SIZE).clamp(1, MAX_AUTHOR_PAGE_SIZE);
+    let profile = match database::get_author_profile(db.clone(), author.clone()).await {
+        Ok(profile) => profile,
+        Err(e) => return db_error(e)
+    };
+    match database::get_author_articles(db, author, query.after, limit).await {
+        Ok(articles) => {
+            // a full page means there may be more
+            let next = if articles.len() as i32 == limit { articles.last().map(|a| a.id) } else { None };
+            HttpResponse::Ok().json(AuthorPage { profile, articles, next })
+        },
+        Err(e) => db_error(e)
+    }
+}
+
+// authors edit their own bio and avatar, the display name is set by admins
+async fn update_profile(db: web::Data<database::DB>, author: identity::RequireRole<identity::Author>, info: web::Json<database::ProfileUpdate>) -> impl Responder {
+    match database::set_author_profile(db, author.credentials.username, info.into_inner()).await {
+        Ok(s) => HttpResponse::Ok().json(Msg { msg: s }),
+        Err(e) => db_error(e)
+    }
+}
+
+#[derive(Deserialize)]
+struct DiffQuery {
+    from: i32,
+    to: i32,
+}
+
+#[derive(Serialize)]
+struct RevisionDiff {
+    from: i32,
+    to: i32,
+    headline_cn: Vec<diff::Chunk>,
+    summary: Vec<diff::Chunk>,
+    article_body: Vec<diff::Chunk>,
+}
+
+// authors change published articles through a new draft and review
+async fn update_article(db: web::Data<database::DB>, publisher: identity::RequireRole<identity::Publisher>, article_id: web::Path<i32>, info: web::Json<database::ArticleUpdate>) -> impl Responder {
+    match database::update_article(db, publisher.credentials.username, article_id.into_inner(), info.into_inner()).await {
+        Ok(revision) => HttpResponse::Ok().json(revision),
+        Err(e) => db_error(e)
+    }
+}
+
+async fn article_revisions(db: web::Data<database::DB>, user: identity::RequireLogin, article_id: web::Path<i32>) -> impl Responder {
+    match database::get_article_revisions(db, user.credentials.username, article_id.into_inner()).await {
+        Ok(revisions) => HttpResponse::Ok().json(revisions),
+        Err(e) => db_error(e)
+    }
+}
+
+async fn article_revision_diff(db: web::Data<database::DB>, user: identity::RequireLogin, article_id: web::Path<i32>, query: web::Query<DiffQuery>) -> impl Responder {
+    let article_id = article_id.into_inner();
+    let username = user.credentials.username;
+    let from = database::get_article_revision(db.clone(), username.clone(), article_id, query.from).await;
+    let to = database::get_article_revision(db, username, article_id, query.to).await;
+    match (from, to) {
+        // long bodies take a while to diff, so keep it off the worker thread
+        (Ok(from), Ok(to)) => match web::block(move || Ok::<_, ()>(RevisionDiff {
+            from: from.id,
+            to: to.id,
+            headline_cn: diff::diff_text(&from.headline_cn, &to.headline_cn),
+            summary: diff::diff_text(&from.summary, &to.summary),
+            article_body: diff::diff_text(&from.article_body, &to.article_body),
+        })).await {
+            Ok(diff) => HttpResponse::Ok().json(diff),
+            Err(_) => HttpResponse::InternalServerError().finish()
+        },
+        (Err(e), _) | (_, Err(e)) => db_error(e)
+    }
+}
+
+async fn restore_article_revision(db: web::Data<database::DB>, publisher: identity::RequireRole<identity::Publisher>, path: web::Path<(i32, i32)>) -> impl Responder {
+    let (article_id, revision) = path.into_inner();
+    match database::restore_article_revision(db, publisher.credentials.username, article_id, revision).await {
+        Ok(revision) => HttpResponse::Ok().json(revision),
+        Err(e) => db_error(e)
+    }
+}
+
+#[derive(Deserialize)]
+struct MarkdownPreview {
+    article_body: String,
+}
+
+#[derive(Serialize)]
+struct RenderedMarkdown {
+    article_html: String,
+}
+
+// lets the editor show the article as it will be rendered while typing
+async fn preview_markdown(info: web::Json<MarkdownPreview>, _author: identity::RequireRole<identity::Author>) -> impl Responder {
+    HttpResponse::Ok().json(RenderedMarkdown { article_html: markdown::render(&info.article_body) })
+}
+
+async fn articles_in_progress(db: web::Data<database::DB>, author: identity::RequireRole<identity::Author>) -> impl Responder {
+    match database::get_temp_article_list(db, author.credentials.username).await {
+        Ok(drafts) => HttpResponse::Ok().json(drafts),
+        Err(e) => db_error(e)
+    }
+}
+
+async fn new_draft(db: web::Data<database::DB>, author: identity::RequireRole<identity::Author>) -> impl Responder {
+    match database::create_temp_article(db, author.credentials.username).await {
+        Ok(uuid) => HttpResponse::Created().json(uuid),
+        Err(e) => db_error(e)
+    }
+}
+
+#[derive(Serialize)]
+struct DraftSaved {
+    msg: String,
+    version: i32,
+}
+
+#[derive(Serialize)]
+struct DraftConflict {
+    msg: String,
+    draft: database::TempArticle,
+}
+
+// reviewers and publishers can read drafts that have been submitted
+async fn draft(db: web::Data<database::DB>, user: identity::RequireLogin, draft_id: web::Path<Uuid>) -> impl Responder {
+    match database::get_temp_article(db, user.credentials.username, draft_id.into_inner()).await {
+        Ok(draft) => HttpResponse::Ok()
+            .header(header::ETAG, format!("\"{}\"", draft.version))
+            .json(draft),
+        Err(e) => db_error(e)
     }
 }
 
-// async fn write_article(db: web::Data<database::DB>, id: Identity) -> impl Responder {
-//     if identity::can_write_article(id) {
-//         let uuid = Uuid::new_v4().to_simple().to_string();
-//         Some(uuid)
-//     } else {
-//         None
-//     }
-// }
+// rejected with 409 and the server copy if the draft moved on since `version`
+async fn update_draft(db: web::Data<database::DB>, author: identity::RequireRole<identity::Author>, draft_id: web::Path<Uuid>, info: web::Json<database::TempArticleUpdate>) -> impl Responder {
+    let draft_id = draft_id.into_inner();
+    let body = info.article_body.clone();
+    let username = author.credentials.username;
+    match database::update_temp_article(db.clone(), username.clone(), draft_id, info.into_inner()).await {
+        Ok(version) => {
+            // comments follow their text, a failure here leaves the old offsets in place
+            if let Some(body) = body {
+                if let Err(e) = database::reanchor_draft_comments(db, draft_id, body).await {
+                    log::warn!("Could not move the comments on draft {}: {}", draft_id, e);
+                }
+            }
+            HttpResponse::Ok()
+                .header(header::ETAG, format!("\"{}\"", version))
+                .json(DraftSaved { msg: "Draft saved".to_string(), version })
+        },
+        Err(BlockingError::Error(database::DBError::ConflictError(msg))) => match database::get_temp_article(db, username, draft_id).await {
+            Ok(draft) => HttpResponse::Conflict()
+                .header(header::ETAG, format!("\"{}\"", draft.version))
+                .json(DraftConflict { msg, draft }),
+            Err(e) => db_error(e)
+        },
+        Err(e) => db_error(e)
+    }
+}
+
+async fn delete_draft(db: web::Data<database::DB>, author: identity::RequireRole<identity::Author>, draft_id: web::Path<Uuid>) -> impl Responder {
+    match database::delete_temp_article(db, author.credentials.username, draft_id.into_inner()).await {
+        Ok(s) => HttpResponse::Ok().json(Msg { msg: s }),
+        Err(e) => db_error(e)
+    }
+}
+
+// without a date_published the article goes live now
+#[derive(Deserialize)]
+struct Publish {
+    date_published: Option<SystemTime>,
+}
+
+async fn publish_draft(db: web::Data<database::DB>, publisher: identity::RequireRole<identity::Publisher>, draft_id: web::Path<Uuid>, info: Option<web::Json<Publish>>) -> impl Responder {
+    let publish_at = info.and_then(|info| info.into_inner().date_published);
+    match database::publish_temp_article(db, publisher.credentials.username, draft_id.into_inner(), publish_at).await {
+        Ok(article_id) => HttpResponse::Created().json(article_id),
+        Err(e) => db_error(e)
+    }
+}
+
+#[derive(Deserialize)]
+struct WorkflowNote {
+    note: Option<String>,
+}
+
+#[derive(Serialize)]
+struct WorkflowStatus {
+    msg: String,
+    status: String,
+}
+
+async fn transition_draft(db: web::Data<database::DB>, user: identity::RequireLogin, draft_id: Uuid, action: &'static str, note: Option<web::Json<WorkflowNote>>) -> HttpResponse {
+    let note = note.and_then(|n| n.into_inner().note);
+    match database::transition_temp_article(db, user.credentials.username, draft_id, action, note).await {
+        Ok(status) => HttpResponse::Ok().json(WorkflowStatus { msg: format!("Draft is {}", status.replace('_', " ")), status }),
+        Err(e) => db_error(e)
+    }
+}
+
+async fn submit_draft(db: web::Data<database::DB>, user: identity::RequireLogin, draft_id: web::Path<Uuid>, note: Option<web::Json<WorkflowNote>>) -> impl Responder {
+    transition_draft(db, user, draft_id.into_inner(), "submit", note).await
+}
+
+async fn start_review(db: web::Data<database::DB>, user: identity::RequireLogin, draft_id: web::Path<Uuid>, note: Option<web::Json<WorkflowNote>>) -> impl Responder {
+    transition_draft(db, user, draft_id.into_inner(), "start_review", note).await
+}
+
+async fn approve_draft(db: web::Data<database::DB>, user: identity::RequireLogin, draft_id: web::Path<Uuid>, note: Option<web::Json<WorkflowNote>>) -> impl Responder {
+    transition_draft(db, user, draft_id.into_inner(), "approve", note).await
+}
+
+async fn request_changes(db: web::Data<database::DB>, user: identity::RequireLogin, draft_id: web::Path<Uuid>, note: Option<web::Json<WorkflowNote>>) -> impl Responder {
+    transition_draft(db, user, draft_id.into_inner(), "request_changes", note).await
+}
+
+// anchor_start and anchor_end are char offsets into the draft body as last saved
+async fn draft_comments(db: web::Data<database::DB>, user: identity::RequireLogin, draft_id: web::Path<Uuid>) -> impl Responder {
+    match database::get_draft_comments(db, user.credentials.username, draft_id.into_inner()).await {
+        Ok(comments) => HttpResponse::Ok().json(comments),
+        Err(e) => db_error(e)
+    }
+}
+
+async fn add_draft_comment(db: web::Data<database::DB>, user: identity::RequireLogin, draft_id: web::Path<Uuid>, info: web::Json<database::NewComment>) -> impl Responder {
+    let draft_id = draft_id.into_inner();
+    let info = info.into_inner();
+    let username = user.credentials.username;
+    if info.body.trim().is_empty() {
+        return HttpResponse::BadRequest().json(Msg { msg: "Comment is empty".to_string() });
+    }
+
+    // replies belong to their thread and are never anchored themselves
+    let anchor = match (info.parent, info.anchor_start, info.anchor_end) {
+        (None, Some(start), Some(end)) => {
+            let draft = match database::get_temp_article(db.clone(), username.clone(), draft_id).await {
+                Ok(draft) => draft,
+                Err(e) => return db_error(e)
+            };
+            match anchor::capture(&draft.article_body.unwrap_or_default(), start, end) {
+                Some(anchor) => Some(anchor),
+                None => return HttpResponse::BadRequest().json(Msg { msg: "Anchor is outside the draft".to_string() })
+            }
+        },
+        (_, None, None) => None,
+        _ => return HttpResponse::BadRequest().json(Msg { msg: "Anchor needs a start and an end on a new thread".to_string() })
+    };
+
+    match database::add_draft_comment(db, username, draft_id, info, anchor).await {
+        Ok(comment_id) => HttpResponse::Created().json(comment_id),
+        Err(e) => db_error(e)
+    }
+}
+
+async fn resolve_comment(db: web::Data<database::DB>, user: identity::RequireLogin, path: web::Path<(Uuid, i32)>) -> impl Responder {
+    set_comment_resolved(db, user, path.into_inner(), true).await
+}
+
+async fn reopen_comment(db: web::Data<database::DB>, user: identity::RequireLogin, path: web::Path<(Uuid, i32)>) -> impl Responder {
+    set_comment_resolved(db, user, path.into_inner(), false).await
+}
 
-async fn articles_in_progress(db: web::Data<database::DB>, id: Identity) -> impl Responder {
-    if identity::can_write_article(id) {
-        unimplemented!()
-    } else {
-        HttpResponse::Unauthorized().finish()
+async fn set_comment_resolved(db: web::Data<database::DB>, user: identity::RequireLogin, (draft_id, comment_id): (Uuid, i32), resolve: bool) -> HttpResponse {
+    match database::set_comment_resolved(db, user.credentials.username, draft_id, comment_id, resolve).await {
+        Ok(s) => HttpResponse::Ok().json(Msg { msg: s }),
+        Err(e) => db_error(e)
     }
 }
 
-async fn index(id: Identity) -> impl Responder {
-    let name = id.identity();
+async fn workflow_queue(db: web::Data<database::DB>, user: identity::RequireLogin, queue: web::Path<String>) -> impl Responder {
+    let queue = match queue.as_str() {
+        "author" => "author",
+        "reviewer" => "reviewer",
+        "publisher" => "publisher",
+        _ => return HttpResponse::NotFound().json(Msg { msg: "Queue does not exist".to_string() })
+    };
+    match database::get_workflow_queue(db, user.credentials.username, queue).await {
+        Ok(items) => HttpResponse::Ok().json(items),
+        Err(e) => db_error(e)
+    }
+}
+
+async fn set_draft_deadlines(db: web::Data<database::DB>, user: identity::RequireLogin, draft_id: web::Path<Uuid>, info: web::Json<database::Deadlines>) -> impl Responder {
+    match database::set_draft_deadlines(db, user.credentials.username, draft_id.into_inner(), info.into_inner()).await {
+        Ok(s) => HttpResponse::Ok().json(Msg { msg: s }),
+        Err(e) => db_error(e)
+    }
+}
+
+#[derive(Deserialize)]
+struct CalendarQuery {
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+}
+
+// the token and url are only shown when the token is issued, after that only a reset gives a new one
+#[derive(Serialize)]
+struct CalendarFeed {
+    token: Option<String>,
+    url: Option<String>,
+    date_created: SystemTime,
+}
+
+const DAY: Duration = Duration::from_secs(60 * 60 * 24);
+// without an end date the calendar shows the next four weeks
+const CALENDAR_DAYS: u32 = 28;
+const MAX_CALENDAR_DAYS: u32 = 366;
+// the feed keeps recent events so they don't vanish from calendar apps as soon as they pass
+const FEED_PAST_DAYS: u32 = 30;
+
+async fn calendar(db: web::Data<database::DB>, user: identity::RequireLogin, query: web::Query<CalendarQuery>) -> impl Responder {
+    let since = query.since.map(SystemTime::from).unwrap_or_else(SystemTime::now);
+    let until = query.until.map(SystemTime::from).unwrap_or(since + DAY * CALENDAR_DAYS);
+    match until.duration_since(since) {
+        Ok(range) if range <= DAY * MAX_CALENDAR_DAYS => (),
+        Ok(_) => return HttpResponse::BadRequest().json(Msg { msg: format!("The range can be at most {} days", MAX_CALENDAR_DAYS) }),
+        Err(_) => return HttpResponse::BadRequest().json(Msg { msg: "until must be after since".to_string() })
+    }
+    match database::get_calendar(db, user.credentials.username, since, until).await {
+        Ok(events) => HttpResponse::Ok().json(events),
+        Err(e) => db_error(e)
+    }
+}
+
+fn calendar_feed(issued: database::CalendarToken) -> CalendarFeed {
+    CalendarFeed {
+        url: issued.token.as_ref().map(|token| format!("{}/api/calendar/{}.ics", *SITE_DOMAIN, token)),
+        token: issued.token,
+        date_created: issued.date_created,
+    }
+}
+
+async fn calendar_token(db: web::Data<database::DB>, user: identity::RequireLogin) -> impl Responder {
+    match database::get_calendar_token(db, user.credentials.username, false).await {
+        Ok(token) => HttpResponse::Ok().json(calendar_feed(token)),
+        Err(e) => db_error(e)
+    }
+}
+
+async fn reset_calendar_token(db: web::Data<database::DB>, user: identity::RequireLogin) -> impl Responder {
+    match database::get_calendar_token(db, user.credentials.username, true).await {
+        Ok(token) => HttpResponse::Ok().json(calendar_feed(token)),
+        Err(e) => db_error(e)
+    }
+}
+
+// calendar apps can't log in, so the secret token in the url stands in for the auth cookie
+async fn calendar_ics(db: web::Data<database::DB>, token: web::Path<String>) -> impl Responder {
+    let username = match database::calendar_user(db.clone(), token.into_inner()).await {
+        Ok(Some(username)) => username,
+        Ok(None) => return HttpResponse::NotFound().json(Msg { msg: "Calendar does not exist".to_string() }),
+        Err(e) => return db_error(e)
+    };
+    let now = SystemTime::now();
+    match database::get_calendar(db, username, now - DAY * FEED_PAST_DAYS, now + DAY * MAX_CALENDAR_DAYS).await {
+        Ok(events) => HttpResponse::Ok()
+            .content_type("text/calendar; charset=utf-8")
+            .body(ical::calendar("Editorial calendar", &events.iter().map(calendar_event).collect::<Vec<_>>())),
+        Err(e) => db_error(e)
+    }
+}
+
+fn calendar_event(event: &database::CalendarEvent) -> ical::Event {
+    let headline = event.headline_cn.as_deref().filter(|h| !h.is_empty()).unwrap_or("Untitled draft");
+    let (summary, id, url) = match (event.article_id, event.draft) {
+        (Some(id), _) => (format!("Publish: {}", headline), id.to_string(), format!("{}/article/{}", *SITE_DOMAIN, id)),
+        (None, Some(draft)) if event.kind == "review" => (format!("Review due: {}", headline), draft.to_string(), format!("{}/write_article/{}", *SITE_DOMAIN, draft)),
+        (None, draft) => (format!("Draft due: {}", headline), draft.unwrap_or_default().to_string(), format!("{}/write_article/{}", *SITE_DOMAIN, draft.unwrap_or_default())),
+    };
+    ical::Event {
+        uid: format!("{}-{}@{}", event.kind, id, *EMAIL_DOMAIN),
+        start: event.date,
+        summary,
+        description: Some(match &event.person {
+            Some(person) => format!("{} ({})", person, event.status.replace('_', " ")),
+            None => event.status.replace('_', " "),
+        }),
+        url: Some(url),
+    }
+}
+
+async fn upload_image(req: HttpRequest, body: web::Bytes, _author: identity::RequireRole<identity::Author>) -> impl Responder {
+    let content_type = req.headers().get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("");
+    match images::multipart_file(content_type, &body) {
+        None => HttpResponse::BadRequest().json(Msg { msg: "No image in upload".to_string() }),
+        Some(file) if file.len() > images::MAX_IMAGE_BYTES => 
+            HttpResponse::PayloadTooLarge().json(Msg { msg: "Image is too large".to_string() }),
+        Some(file) => match images::sniff(file) {
+            None => HttpResponse::UnsupportedMediaType().json(Msg { msg: "Not a supported image type".to_string() }),
+            Some(extension) => match images::store(file, extension) {
+                Ok(filename) => HttpResponse::Created().json(filename),
+                Err(e) => HttpResponse::InternalServerError().json(Msg { msg: e.to_string() })
+            }
+        }
+    }
+}
+
+// the page gets the user's current roles, and a cookie for an ended session is removed
+async fn index(req: HttpRequest, id: Identity) -> impl Responder {
+    let name = match identity::current_credentials(&req).await {
+        // explicitly unwrap to null string if json fails, because this will show up in Elm as not logged in
+        Some(credentials) => Some(serde_json::to_string(&credentials).unwrap_or_else(|_| "null".to_string())),
+        None => {
+            if id.identity().is_some() {
+                id.forget();
+            }
+            None
+        }
+    };
     HttpResponse::Ok().body(html::elm_page(&name))
 }
 
@@ -131,17 +1219,54 @@ async fn font(info: web::Path<String>) -> Result<fs::NamedFile> {
     Ok(fs::NamedFile::open(format!("static/fonts/{}", name))?)
 }
 
-async fn image(info: web::Path<String>) -> Result<fs::NamedFile> {
+#[derive(Deserialize)]
+struct ImageQuery {
+    w: Option<u32>,
+    fmt: Option<String>,
+}
+
+async fn image(info: web::Path<String>, query: web::Query<ImageQuery>) -> Result<fs::NamedFile> {
     let name = info.into_inner();
-    match fs::NamedFile::open(format!("static/images/{}", name)) {
+    let ImageQuery { w, fmt } = query.into_inner();
+
+    if w.is_some() || fmt.is_some() {
+        if !images::allowed_variant(w, fmt.as_deref()) {
+            return Err(error::ErrorBadRequest("image size or format not allowed"));
+        }
+        let variant_name = name.clone();
+        match web::block(move || images::variant(&variant_name, w, fmt.as_deref())).await {
+            Ok(path) => return Ok(fs::NamedFile::open(path)?),
+            // images the server can't decode, e.g. GIFs, are served as uploaded
+            Err(BlockingError::Error(e)) if e.kind() == std::io::ErrorKind::InvalidData => (),
+            Err(BlockingError::Error(e)) if e.kind() == std::io::ErrorKind::NotFound => (),
+            // the original is still served, but a cache that can't be written slows every request
+            Err(e) => log::warn!("Could not make image variant of {}: {}", name, e)
+        }
+    }
+
+    match fs::NamedFile::open(format!("{}/{}", *images::IMAGE_DIR, name)) {
         Ok(img) => Ok(img),
-        Err(_) => Ok(fs::NamedFile::open("static/images/placeholder.jpg")?) 
+        Err(_) => Ok(fs::NamedFile::open(images::PLACEHOLDER)?) 
     }
 }
 
 
 // PROGRAM LOGIC
 
+// longest wait between checks, so newly scheduled articles are picked up
+const PUBLISH_POLL: Duration = Duration::from_secs(60);
+
+// visibility is checked against datePublished on every request,
+// this only records each article going live in the logs
+async fn record_published_articles(db: web::Data<database::DB>) {
+    loop {
+        let wait = match database::record_published_articles(db.clone()).await {
+            Ok(Some(next)) => next.duration_since(SystemTime::now()).unwrap_or_default(),
+            _ => PUBLISH_POLL,
+        };
+        time::delay_for(wait.max(Duration::from_secs(1)).min(PUBLISH_POLL)).await;
+    }
+}
 
 // REQUIRED ENV VARIABLES
 lazy_static! {
@@ -149,6 +1274,8 @@ lazy_static! {
     static ref MAILGUN_KEY: String = std::env::var("MAILGUN_KEY").unwrap_or_else(|_| "0000".repeat(8));
     static ref EMAIL_DOMAIN: String = std::env::var("EMAIL_DOMAIN").unwrap_or_else(|_| "example.com".to_string());
     static ref SITE_DOMAIN: String = std::env::var("SITE_DOMAIN").unwrap_or_else(|_| "example.com".to_string());
+    // set OPEN_REGISTRATION=false to only allow accounts created from staff invitations
+    static ref OPEN_REGISTRATION: bool = std::env::var("OPEN_REGISTRATION").map_or(true, |v| v != "false" && v != "0");
 }
 
 #[actix_rt::main]
@@ -170,8 +1297,18 @@ async fn main() -> std::io::Result<()> {
     thread::spawn(move || { 
         let x: String = database::set_up(db2.get().unwrap()); 
         println!("{}", x);
+        match db2.get() {
+            Ok(conn) => match database::count_text(conn) {
+                Ok(0) => (),
+                Ok(counted) => log::info!("Counted the text of {} articles and drafts", counted),
+                Err(e) => log::warn!("Could not count the text of older articles: {}", e)
+            },
+            Err(e) => log::warn!("Could not count the text of older articles: {}", e)
+        }
     });
 
+    actix_rt::spawn(record_published_articles(web::Data::new(db.clone())));
+
     // Run the server
     HttpServer::new(move || { 
         App::new()
@@ -183,13 +1320,81 @@ async fn main() -> std::io::Result<()> {
                     .secure(false)))
             .data(db.clone())
             .service(web::scope("/api")
+                // article bodies can be longer than the 32kB default
+                .app_data(web::JsonConfig::default().limit(1024 * 1024))
                 .route("/hello", web::get().to(hello))
                 .route("/login", web::post().to(login))
+                .route("/login/two_factor", web::post().to(login_two_factor))
                 .route("/register", web::post().to(register)) 
+                .route("/confirm/resend", web::post().to(resend_confirmation))
                 .route("/confirm/{token}", web::get().to(confirm))
+                .route("/invitations", web::get().to(staff_invitations))
+                .route("/invitations", web::post().to(invite_staff))
+                .route("/invitations/{id}", web::delete().to(revoke_staff_invitation))
+                .route("/invitations/{token}/accept", web::post().to(accept_staff_invitation))
+                .route("/password_reset", web::post().to(forgot_password))
+                .route("/password_reset/{token}", web::post().to(reset_password))
                 .route("/logout", web::post().to(logout))
+                .route("/sessions", web::get().to(sessions))
+                .route("/sessions", web::delete().to(revoke_sessions))
+                .route("/sessions/{id}", web::delete().to(revoke_session))
+                .route("/two_factor", web::get().to(two_factor_status))
+                .route("/two_factor", web::post().to(start_two_factor))
+                .route("/two_factor/confirm", web::post().to(confirm_two_factor))
+                .route("/two_factor/recovery_codes", web::post().to(reset_recovery_codes))
+                .route("/two_factor/disable", web::post().to(disable_two_factor))
+                .route("/roles/{role}/two_factor", web::put().to(require_two_factor))
+                .route("/roles/{role}/two_factor", web::delete().to(unrequire_two_factor))
+                .route("/lockouts", web::get().to(lockouts))
+                .route("/lockouts/{scope}/{key}", web::delete().to(clear_lockout))
+                .route("/logs", web::get().to(logs))
+                .route("/users", web::get().to(users))
+                .route("/users/{id}", web::get().to(user))
+                .route("/users/{id}/roles/{role}", web::put().to(grant_role))
+                .route("/users/{id}/roles/{role}", web::delete().to(revoke_role))
+                .route("/users/{id}/display_name", web::put().to(set_display_name))
+                .route("/users/{id}/deactivate", web::post().to(deactivate_user))
+                .route("/users/{id}/activate", web::post().to(activate_user))
                 .route("/articles", web::get().to(articles))
                 .route("/article/{id}", web::get().to(article))
+                .route("/article/{id}", web::put().to(update_article))
+                .route("/article/{id}/disable", web::post().to(disable_article))
+                .route("/article/{id}/enable", web::post().to(enable_article))
+                .route("/article/{id}/schedule", web::post().to(schedule_article))
+                .route("/article/{id}/bylines", web::put().to(set_article_bylines))
+                .route("/article/{id}/revisions", web::get().to(article_revisions))
+                .route("/article/{id}/revisions/diff", web::get().to(article_revision_diff))
+                .route("/article/{id}/revisions/{revision}/restore", web::post().to(restore_article_revision))
+                .route("/calendar", web::get().to(calendar))
+                .route("/calendar/token", web::get().to(calendar_token))
+                .route("/calendar/token/reset", web::post().to(reset_calendar_token))
+                .route("/calendar/{token}.ics", web::get().to(calendar_ics))
+                .route("/markdown/preview", web::post().to(preview_markdown))
+                .route("/authors/{username}", web::get().to(author_profile))
+                .route("/profile", web::put().to(update_profile))
+                .route("/guests", web::get().to(guest_authors))
+                .route("/guests", web::post().to(new_guest_author))
+                .route("/guests/{id}", web::put().to(update_guest_author))
+                .route("/drafts", web::get().to(articles_in_progress))
+                .route("/drafts", web::post().to(new_draft))
+                .route("/drafts/{id}", web::get().to(draft))
+                .route("/drafts/{id}", web::put().to(update_draft))
+                .route("/drafts/{id}", web::delete().to(delete_draft))
+                .route("/drafts/{id}/submit", web::post().to(submit_draft))
+                .route("/drafts/{id}/review", web::post().to(start_review))
+                .route("/drafts/{id}/approve", web::post().to(approve_draft))
+                .route("/drafts/{id}/request_changes", web::post().to(request_changes))
+                .route("/drafts/{id}/publish", web::post().to(publish_draft))
+                .route("/drafts/{id}/deadlines", web::put().to(set_draft_deadlines))
+                .route("/drafts/{id}/comments", web::get().to(draft_comments))
+                .route("/drafts/{id}/comments", web::post().to(add_draft_comment))
+                .route("/drafts/{id}/comments/{comment}/resolve", web::post().to(resolve_comment))
+                .route("/drafts/{id}/comments/{comment}/reopen", web::post().to(reopen_comment))
+                .route("/queue/{queue}", web::get().to(workflow_queue))
+                .service(web::resource("/images")
+                    // leave room for the multipart headers around the file
+                    .app_data(web::PayloadConfig::new(images::MAX_IMAGE_BYTES + 64 * 1024))
+                    .route(web::post().to(upload_image)))
             )
             .service(web::scope("/fonts")
                 .route("/{name}", web::get().to(font))
@@ -0,0 +1,47 @@
+use std::collections::{HashMap, HashSet};
+use ammonia::{Builder, UrlRelative};
+use pulldown_cmark::{html, Event, Options, Parser};
+
+lazy_static! {
+    // everything not listed here is removed from the rendered html
+    static ref SANITIZER: Builder<'static> = {
+        let tags: HashSet<&str> = [
+            "h1", "h2", "h3", "h4", "h5", "h6",
+            "p", "br", "hr",
+            "em", "strong", "del",
+            "a", "img",
+            "ul", "ol", "li",
+            "blockquote",
+        ].iter().cloned().collect();
+
+        let mut attributes: HashMap<&str, HashSet<&str>> = HashMap::new();
+        attributes.insert("a", ["href", "title"].iter().cloned().collect());
+        attributes.insert("img", ["src", "alt", "title"].iter().cloned().collect());
+        attributes.insert("ol", ["start"].iter().cloned().collect());
+
+        let mut builder = Builder::empty();
+        builder
+            .tags(tags)
+            .tag_attributes(attributes)
+            .url_schemes(["http", "https", "mailto"].iter().cloned().collect())
+            .url_relative(UrlRelative::PassThrough)
+            .link_rel(Some("noopener noreferrer nofollow"))
+            .strip_comments(true);
+        builder
+    };
+}
+
+// renders an articleBody to html that is safe to insert into the page
+pub fn render(source: &str) -> String {
+    let parser = Parser::new_ext(source, Options::ENABLE_STRIKETHROUGH)
+        // raw html in the source is shown as text rather than trusted
+        .map(|event| match event {
+            Event::Html(raw) => Event::Text(raw),
+            event => event,
+        });
+
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, parser);
+
+    SANITIZER.clean(&unsafe_html).to_string()
+}
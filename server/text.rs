@@ -0,0 +1,71 @@
+use serde::{Serialize};
+
+// average reading speeds, CJK characters and words per minute
+const CJK_PER_MINUTE: f64 = 300.0;
+const WORDS_PER_MINUTE: f64 = 200.0;
+
+#[derive(Serialize, PartialEq, Clone, Copy, Debug, Default)]
+pub struct TextStats {
+    pub cjk_characters: i32,
+    pub words: i32,
+    pub word_count: i32,
+    pub reading_minutes: i32,
+}
+
+// every Han or kana character counts as one word, since Chinese and Japanese aren't written with spaces,
+// runs of letters and digits count as one word, which splits Hangul on spaces like Latin text
+pub fn stats(text: &str) -> TextStats {
+    let mut cjk_characters = 0;
+    let mut words = 0;
+    let mut in_word = false;
+
+    for c in text.chars() {
+        if is_han(c) || is_kana(c) {
+            cjk_characters += 1;
+            in_word = false;
+        } else if c.is_alphanumeric() || (in_word && (c == '\'' || c == '’' || c == '-')) {
+            if !in_word {
+                words += 1;
+                in_word = true;
+            }
+        } else {
+            in_word = false;
+        }
+    }
+
+    from_counts(cjk_characters, cjk_characters + words)
+}
+
+// the stats for an article from its stored cjkCharacters and wordCount, without loading the body
+pub fn from_counts(cjk_characters: i32, word_count: i32) -> TextStats {
+    let words = (word_count - cjk_characters).max(0);
+    let minutes = cjk_characters as f64 / CJK_PER_MINUTE + words as f64 / WORDS_PER_MINUTE;
+
+    TextStats {
+        cjk_characters,
+        words,
+        word_count: cjk_characters + words,
+        reading_minutes: if cjk_characters + words > 0 { minutes.ceil().max(1.0) as i32 } else { 0 },
+    }
+}
+
+pub fn is_han(c: char) -> bool {
+    matches!(c as u32,
+        0x3400..=0x4DBF         // CJK Unified Ideographs Extension A
+        | 0x4E00..=0x9FFF       // CJK Unified Ideographs
+        | 0xF900..=0xFAFF       // CJK Compatibility Ideographs
+        | 0x20000..=0x2A6DF     // Extension B
+        | 0x2A700..=0x2EBEF     // Extensions C to F
+        | 0x2F800..=0x2FA1F     // CJK Compatibility Ideographs Supplement
+        | 0x30000..=0x323AF     // Extensions G and H
+    )
+}
+
+pub fn is_kana(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x309F         // Hiragana
+        | 0x30A0..=0x30FF       // Katakana
+        | 0x31F0..=0x31FF       // Katakana Phonetic Extensions
+        | 0xFF66..=0xFF9D       // Halfwidth Katakana
+    )
+}
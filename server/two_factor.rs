@@ -0,0 +1,28 @@
+use qrcode::QrCode;
+use qrcode::render::svg;
+
+// the otpauth:// uri authenticator apps read from the QR code, the defaults
+// (SHA1, 6 digits, 30 seconds) match the TOTP checks in the database
+pub fn provisioning_uri(issuer: &str, username: &str, secret: &str) -> String {
+    format!("otpauth://totp/{}:{}?secret={}&issuer={}",
+        uri_component(issuer),
+        uri_component(username),
+        secret,
+        uri_component(issuer))
+}
+
+// None if the uri is too long for a QR code, which it can't be for a username
+pub fn qr_svg(uri: &str) -> Option<String> {
+    QrCode::new(uri).ok()
+        .map(|code| code.render::<svg::Color>().min_dimensions(200, 200).build())
+}
+
+// RFC 3986 unreserved characters are kept, and @ since usernames are email addresses
+fn uri_component(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'@' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}